@@ -0,0 +1,290 @@
+//! Catacomb IPC interface.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::{env, fs};
+
+use clap::{ArgEnum, Subcommand};
+use serde::{Deserialize, Serialize};
+
+/// Environment variable containing the IPC socket path.
+pub const SOCKET_ENV: &str = "CATACOMB_SOCKET";
+
+/// IPC message sent to the compositor.
+#[derive(Subcommand, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum IpcMessage {
+    /// Open the application overview.
+    Overview,
+    /// Hide all windows and return to the home screen.
+    Home,
+    /// Show a window as the primary window.
+    Primary {
+        /// Index of the window in the window list.
+        index: usize,
+    },
+    /// Show a window as the secondary window.
+    Secondary {
+        /// Index of the window in the window list.
+        index: usize,
+    },
+    /// Ask a window to close.
+    Close {
+        /// Index of the window in the window list.
+        index: usize,
+    },
+    /// Change the output orientation.
+    Orientation {
+        #[clap(arg_enum)]
+        orientation: Orientation,
+    },
+    /// Show the internal output's content on all external outputs.
+    Mirror {
+        /// Enable or disable mirroring.
+        #[clap(parse(try_from_str))]
+        enabled: bool,
+    },
+    /// Toggle the touch position indicator.
+    TouchDebug,
+    /// Start a new process.
+    Spawn {
+        /// Program to execute.
+        program: String,
+        /// Arguments passed to the program.
+        #[clap(allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Print compositor events as they occur.
+    Subscribe,
+    /// Write the next rendered frame to a PNG file.
+    Screenshot {
+        /// Output file path.
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Record all input events to a file.
+    Record {
+        /// Recording file path.
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Stop the active input recording.
+    StopRecording,
+    /// Replay a recording of input events.
+    Replay {
+        /// Recording file path.
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Change an output's resolution and refresh rate.
+    Mode {
+        /// Output name, like `HDMIA-1`.
+        output: String,
+        /// Mode like `1920x1080@60`, resets to the default mode if omitted.
+        mode: Option<OutputMode>,
+    },
+}
+
+/// IPC reply sent by the compositor.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum IpcResponse {
+    /// Message was processed successfully.
+    Ok,
+    /// Message could not be processed.
+    Error { message: String },
+}
+
+impl IpcResponse {
+    /// Create an error response.
+    pub fn error(message: impl Into<String>) -> Self {
+        IpcResponse::Error { message: message.into() }
+    }
+}
+
+/// Compositor state change.
+///
+/// After a [`IpcMessage::Subscribe`] was acknowledged, these events are sent
+/// to the subscriber as they occur.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum IpcEvent {
+    /// New window was mapped.
+    WindowCreated { window: WindowInfo },
+    /// Window was destroyed.
+    WindowDestroyed { window: WindowInfo },
+    /// Title or app ID of a window was changed.
+    WindowChanged { previous: WindowInfo, window: WindowInfo },
+    /// Primary window was changed.
+    PrimaryChanged { window: Option<WindowInfo> },
+    /// Secondary window was changed.
+    SecondaryChanged { window: Option<WindowInfo> },
+    /// Active view was changed.
+    ViewChanged { view: View },
+    /// Output orientation was changed.
+    OrientationChanged { orientation: Orientation },
+    /// Space reserved by layer shell surfaces was changed.
+    ExclusiveSpaceChanged { top: i32, right: i32, bottom: i32, left: i32 },
+    /// Gesture bound to the `ipc` action was completed.
+    ///
+    /// The gesture is identified by its configuration key, like
+    /// `gestures.edges.left`.
+    GestureCompleted { gesture: String },
+}
+
+/// Toplevel window description.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WindowInfo {
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+}
+
+/// Compositor window arrangement.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum View {
+    Workspace,
+    Overview,
+    DragAndDrop,
+}
+
+/// Output orientation.
+#[derive(ArgEnum, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    Portrait,
+    InversePortrait,
+    Landscape,
+    InverseLandscape,
+}
+
+/// Output resolution and refresh rate.
+///
+/// Modes are represented as `WIDTHxHEIGHT[@REFRESH]` strings, with the
+/// refresh rate in Hz.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct OutputMode {
+    pub width: u32,
+    pub height: u32,
+    /// Refresh rate, the highest available one is used if omitted.
+    pub refresh: Option<u32>,
+}
+
+impl FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        let error = || format!("invalid mode {mode:?}, expected WIDTHxHEIGHT[@REFRESH]");
+
+        let (size, refresh) = match mode.split_once('@') {
+            Some((size, refresh)) => (size, Some(refresh.parse().map_err(|_| error())?)),
+            None => (mode, None),
+        };
+
+        let (width, height) = size.split_once('x').ok_or_else(error)?;
+        let width = width.parse().map_err(|_| error())?;
+        let height = height.parse().map_err(|_| error())?;
+
+        if width == 0 || height == 0 || refresh == Some(0) {
+            return Err(error());
+        }
+
+        Ok(Self { width, height, refresh })
+    }
+}
+
+impl TryFrom<String> for OutputMode {
+    type Error = String;
+
+    fn try_from(mode: String) -> Result<Self, Self::Error> {
+        mode.parse()
+    }
+}
+
+impl From<OutputMode> for String {
+    fn from(mode: OutputMode) -> Self {
+        mode.to_string()
+    }
+}
+
+impl Display for OutputMode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)?;
+
+        if let Some(refresh) = self.refresh {
+            write!(f, "@{refresh}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// IPC socket path for a Wayland socket.
+pub fn socket_path(wayland_display: &str) -> Option<PathBuf> {
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")?;
+    let file_name = format!("catacomb-{wayland_display}.sock");
+    Some(PathBuf::from(runtime_dir).join(file_name))
+}
+
+/// Send a message to the running compositor.
+pub fn send_message(message: &IpcMessage) -> Result<IpcResponse, Box<dyn Error>> {
+    let mut stream = connect()?;
+
+    let mut json = serde_json::to_string(message)?;
+    json.push('\n');
+    stream.write_all(json.as_bytes())?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+
+    Ok(serde_json::from_str(&reply)?)
+}
+
+/// Subscribe to compositor events.
+///
+/// This will call `callback` for every received event until the compositor
+/// closes the connection.
+pub fn subscribe<F: FnMut(IpcEvent)>(mut callback: F) -> Result<(), Box<dyn Error>> {
+    let mut stream = connect()?;
+
+    let mut json = serde_json::to_string(&IpcMessage::Subscribe)?;
+    json.push('\n');
+    stream.write_all(json.as_bytes())?;
+
+    let mut lines = BufReader::new(stream).lines();
+
+    // Ensure the subscription was accepted.
+    let reply = lines.next().ok_or("connection closed")??;
+    if let IpcResponse::Error { message } = serde_json::from_str(&reply)? {
+        return Err(message.into());
+    }
+
+    for line in lines {
+        callback(serde_json::from_str(&line?)?);
+    }
+
+    Ok(())
+}
+
+/// Connect to the IPC socket of the running compositor.
+fn connect() -> Result<UnixStream, Box<dyn Error>> {
+    let path = match env::var_os(SOCKET_ENV) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let wayland_display = env::var("WAYLAND_DISPLAY")
+                .map_err(|_| format!("neither {SOCKET_ENV} nor WAYLAND_DISPLAY is set"))?;
+            socket_path(&wayland_display).ok_or("XDG_RUNTIME_DIR is not set")?
+        },
+    };
+
+    if fs::metadata(&path).is_err() {
+        return Err(format!("socket {path:?} does not exist").into());
+    }
+
+    Ok(UnixStream::connect(path)?)
+}
@@ -0,0 +1,53 @@
+use std::{env, process};
+
+use catacomb_ipc::{IpcMessage, IpcResponse};
+use clap::Parser;
+
+/// Send commands to a running Catacomb instance.
+#[derive(Parser, Debug)]
+#[clap(author, version)]
+struct Options {
+    #[clap(subcommand)]
+    message: IpcMessage,
+}
+
+fn main() {
+    let mut options = Options::parse();
+
+    // Print events until the compositor is shut down.
+    if options.message == IpcMessage::Subscribe {
+        let result = catacomb_ipc::subscribe(|event| match serde_json::to_string(&event) {
+            Ok(json) => println!("{json}"),
+            Err(err) => eprintln!("Error: {err}"),
+        });
+
+        if let Err(err) = result {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+
+        return;
+    }
+
+    // Resolve paths relative to the client's working directory.
+    if let IpcMessage::Screenshot { path }
+    | IpcMessage::Record { path }
+    | IpcMessage::Replay { path } = &mut options.message
+    {
+        if let Ok(working_directory) = env::current_dir() {
+            *path = working_directory.join(&path);
+        }
+    }
+
+    match catacomb_ipc::send_message(&options.message) {
+        Ok(IpcResponse::Ok) => (),
+        Ok(IpcResponse::Error { message }) => {
+            eprintln!("Error: {message}");
+            process::exit(1);
+        },
+        Err(err) => {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        },
+    }
+}
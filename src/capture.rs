@@ -0,0 +1,68 @@
+//! Framebuffer capture.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use png::{BitDepth, ColorType, Encoder};
+use smithay::backend::renderer::gles2::{ffi, Gles2Error, Gles2Renderer};
+#[cfg(test)]
+use smithay::utils::Point;
+use smithay::utils::{Physical, Size};
+
+/// RGBA pixels read back from a framebuffer.
+pub struct Capture {
+    size: Size<i32, Physical>,
+    pixels: Vec<u8>,
+}
+
+impl Capture {
+    /// Read the currently bound framebuffer.
+    ///
+    /// Rows are stored in framebuffer memory order, which matches the
+    /// scanout buffer of offscreen and DRM rendering.
+    pub fn read(
+        renderer: &mut Gles2Renderer,
+        size: Size<i32, Physical>,
+    ) -> Result<Self, Gles2Error> {
+        let mut pixels = vec![0; size.w as usize * size.h as usize * 4];
+
+        renderer.with_context(|_, gl| unsafe {
+            gl.PixelStorei(ffi::PACK_ALIGNMENT, 1);
+            gl.ReadPixels(
+                0,
+                0,
+                size.w,
+                size.h,
+                ffi::RGBA,
+                ffi::UNSIGNED_BYTE,
+                pixels.as_mut_ptr().cast(),
+            );
+        })?;
+
+        Ok(Self { size, pixels })
+    }
+
+    /// RGBA value of a single pixel.
+    #[cfg(test)]
+    pub fn pixel(&self, position: impl Into<Point<i32, Physical>>) -> [u8; 4] {
+        let position = position.into();
+        let start = (position.y * self.size.w + position.x) as usize * 4;
+        self.pixels[start..start + 4].try_into().unwrap()
+    }
+
+    /// Write the capture to a PNG file.
+    pub fn write_png(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = BufWriter::new(File::create(path)?);
+
+        let mut encoder = Encoder::new(file, self.size.w as u32, self.size.h as u32);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+
+        Ok(())
+    }
+}
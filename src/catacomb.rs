@@ -1,23 +1,27 @@
 //! Catacomb compositor state.
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::time::Duration;
-use std::{env, io, mem};
+use std::{cmp, env, io, mem};
 
+use catacomb_ipc::OutputMode;
+use log::{error, info, warn};
 use server_decoration::server::org_kde_kwin_server_decoration_manager::Mode;
-use smithay::backend::renderer::gles2::{Gles2Frame, Gles2Renderer};
+use smithay::backend::renderer::gles2::{ffi, Gles2Frame, Gles2Renderer};
 use smithay::backend::renderer::Frame;
 use smithay::reexports::calloop::generic::Generic;
 use smithay::reexports::calloop::{EventLoop, Interest, Mode as TriggerMode, PostAction};
 use smithay::reexports::wayland_protocols::misc::server_decoration;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::Display;
-use smithay::utils::{Physical, Rectangle};
+use smithay::utils::{Logical, Physical, Rectangle};
 use smithay::wayland::input_method::{InputMethodHandle, InputMethodSeatTrait};
-use smithay::wayland::output::xdg;
-use smithay::wayland::seat::{KeyboardHandle, Seat, XkbConfig};
+use smithay::wayland::output::{xdg, Mode as WaylandMode};
+use smithay::wayland::seat::{KeyboardHandle, PointerHandle, Seat, XkbConfig};
 use smithay::wayland::shell::legacy::decoration as kde_decoration;
 use smithay::wayland::shell::legacy::decoration::KdeDecorationRequest;
 use smithay::wayland::shell::xdg::decoration;
@@ -25,12 +29,18 @@ use smithay::wayland::text_input::{TextInputHandle, TextInputSeatTrait};
 use smithay::wayland::virtual_keyboard::VirtualKeyboardHandle;
 use smithay::wayland::{data_device, input_method, shm, text_input, SERIAL_COUNTER};
 
-use crate::drawing::{Graphics, MAX_DAMAGE_AGE};
-use crate::input::TouchState;
-use crate::orientation::{Accelerometer, AccelerometerSource};
+use crate::capture::Capture;
+use crate::config::{self, Config, DeviceConfig, SwitchAction};
+use crate::cursor::Cursor;
+use crate::drawing::{Graphics, Texture};
+use crate::geometry::Vector;
+use crate::input::{Switches, TouchState};
+use crate::ipc_server::Subscribers;
+use crate::orientation::{Accelerometer, AccelerometerSource, Orientation};
 use crate::output::Output;
-use crate::shell;
+use crate::recording::{InputReplay, Recorder};
 use crate::window::Windows;
+use crate::{daemon, ipc_server, logging, shell};
 
 /// Shared compositor state.
 pub struct Catacomb<B> {
@@ -38,16 +48,50 @@ pub struct Catacomb<B> {
     pub input_method: InputMethodHandle,
     pub text_input: TextInputHandle,
     pub keyboard: KeyboardHandle,
+    pub pointer: PointerHandle,
+    pub cursor: Cursor,
     pub touch_state: TouchState,
+    pub ipc_subscribers: Subscribers,
+    pub pending_captures: Vec<PendingCapture>,
+    pub input_recorder: Option<Recorder>,
+    pub input_replay: InputReplay,
     pub seat_name: String,
     pub terminated: bool,
+    pub config: Config,
     pub windows: Windows,
     pub output: Output,
+    pub external_outputs: Vec<ExternalOutput>,
     pub backend: B,
 
-    last_focus: Option<WlSurface>,
+    /// Output modes requested over IPC, taking precedence over the config.
+    pub mode_overrides: HashMap<String, OutputMode>,
+
+    /// Turn left mouse button presses into touch input.
+    pub touch_emulation: bool,
+
+    /// Show the internal output's content on external outputs.
+    mirroring: bool,
+    mirror_texture: Option<Texture>,
+
+    /// External keyboards and pointers, by device ID.
+    peripherals: Vec<String>,
+
+    /// Settings of configured input devices, by device ID.
+    pub device_configs: HashMap<String, DeviceConfig>,
+
+    /// Tile windows on the first external output.
+    docked: bool,
+
+    /// Lid and tablet-mode switch states.
+    pub switches: Switches,
+
+    /// Last orientation reported by the accelerometer or IPC.
+    pub requested_orientation: Orientation,
+
+    pub last_focus: Option<WlSurface>,
     graphics: Graphics,
     touch_debug: bool,
+    back_indicator: Option<Rectangle<i32, Logical>>,
     damage: Damage,
 
     // NOTE: Must be last field to ensure it's dropped after any global.
@@ -56,18 +100,58 @@ pub struct Catacomb<B> {
 
 impl<B: Backend + 'static> Catacomb<B> {
     /// Initialize the compositor.
-    pub fn new(event_loop: &mut EventLoop<Self>, backend: B) -> Self {
+    pub fn new(
+        event_loop: &mut EventLoop<Self>,
+        config: Config,
+        startup: Startup,
+        backend: B,
+    ) -> Result<Self, Box<dyn Error>> {
         let mut display = Display::new();
 
         // Create our Wayland socket.
-        let socket_name = &mut display
-            .add_socket_auto()
-            .expect("wayland socket")
-            .into_string()
-            .expect("wayland socket name");
+        let socket_name = match startup.socket {
+            Some(socket_name) => {
+                display.add_socket(Some(&socket_name)).map_err(|err| {
+                    format!("Unable to create Wayland socket {socket_name:?}: {err}")
+                })?;
+                socket_name
+            },
+            None => display
+                .add_socket_auto()
+                .map_err(|err| format!("Unable to create Wayland socket: {err}"))?
+                .into_string()
+                .map_err(|name| format!("Invalid Wayland socket name: {name:?}"))?,
+        };
         env::set_var("WAYLAND_DISPLAY", &socket_name);
-        println!("Wayland socket: {socket_name}");
+        info!("Wayland socket: {socket_name}");
+
+        // Create our IPC socket.
+        match ipc_server::spawn_ipc_socket(event_loop.handle(), &socket_name) {
+            Ok(socket_path) => env::set_var(catacomb_ipc::SOCKET_ENV, socket_path),
+            Err(err) => error!("Unable to create IPC socket: {err}"),
+        }
 
+        let catacomb = Self::with_display(event_loop, display, config, backend);
+
+        // Start the session command now that clients can connect.
+        if let Some((program, args)) = startup.command.split_first() {
+            if let Err(err) = daemon::spawn(program, args) {
+                error!("Unable to spawn {program:?}: {err}");
+            }
+        }
+
+        Ok(catacomb)
+    }
+
+    /// Initialize the compositor without creating any sockets.
+    ///
+    /// Clients must be added to the display manually.
+    pub fn with_display(
+        event_loop: &mut EventLoop<Self>,
+        mut display: Display,
+        config: Config,
+        backend: B,
+    ) -> Self {
         // Subscribe to Wayland socket events.
         event_loop
             .handle()
@@ -81,10 +165,10 @@ impl<B: Backend + 'static> Catacomb<B> {
         shell::init::<B>(&mut display);
 
         // Advertise support for rendering from CPU-based shared memory buffers.
-        shm::init_shm_global(&mut display, Vec::new(), None);
+        shm::init_shm_global(&mut display, Vec::new(), logging::smithay());
 
         // Force server-side decorations.
-        decoration::init_xdg_decoration_manager(&mut display, |_, _| {}, None);
+        decoration::init_xdg_decoration_manager(&mut display, |_, _| {}, logging::smithay());
         kde_decoration::init_kde_decoration_manager(
             &mut display,
             |request| match request {
@@ -92,17 +176,17 @@ impl<B: Backend + 'static> Catacomb<B> {
                 KdeDecorationRequest::RequestMode { decoration, mode, .. } => decoration.mode(mode),
                 _ => (),
             },
-            None,
+            logging::smithay(),
         );
 
         // Initialize input.
         let seat_name = backend.seat_name();
-        let (mut seat, _) = Seat::new(&mut display, seat_name.clone(), None);
+        let (mut seat, _) = Seat::new(&mut display, seat_name.clone(), logging::smithay());
         data_device::init_data_device(
             &mut display,
             |_| {},
             data_device::default_action_chooser,
-            None,
+            logging::smithay(),
         );
         let keyboard = seat
             .add_keyboard(XkbConfig::default(), 200, 25, |seat, focused_surface| {
@@ -113,6 +197,8 @@ impl<B: Backend + 'static> Catacomb<B> {
             })
             .expect("adding keyboard");
         let touch = seat.add_touch();
+        let cursor = Cursor::new();
+        let pointer = seat.add_pointer(cursor.image_callback());
 
         // Initialize IME and virtual keyboard.
         input_method::init_input_method_manager_global(&mut display);
@@ -127,24 +213,47 @@ impl<B: Backend + 'static> Catacomb<B> {
         });
 
         // XDG output protocol.
-        xdg::init_xdg_output_manager(&mut display, None);
+        xdg::init_xdg_output_manager(&mut display, logging::smithay());
+
+        // Apply configuration file changes.
+        if let Some(path) = config.path.clone() {
+            config::watch(event_loop.handle(), path);
+        }
 
         Self {
             touch_state: TouchState::new(event_loop.handle(), touch),
-            output: Output::new_dummy(&mut display),
+            output: Output::new_dummy(&mut display, config.output.scale),
+            damage: Damage::new(config.output.max_damage_age),
             display: Rc::new(RefCell::new(display)),
             windows: Windows::new(),
+            mirroring: config.output.mirror,
             virtual_keyboard,
             input_method,
             text_input,
             seat_name,
             keyboard,
+            pointer,
+            cursor,
             backend,
+            config,
+            external_outputs: Default::default(),
+            mode_overrides: Default::default(),
+            mirror_texture: Default::default(),
+            touch_emulation: Default::default(),
+            peripherals: Default::default(),
+            device_configs: Default::default(),
+            docked: Default::default(),
+            switches: Default::default(),
+            requested_orientation: Orientation::Portrait,
+            ipc_subscribers: Default::default(),
+            pending_captures: Default::default(),
+            input_replay: InputReplay::new(event_loop.handle()),
+            input_recorder: Default::default(),
             touch_debug: Default::default(),
+            back_indicator: Default::default(),
             last_focus: Default::default(),
             terminated: Default::default(),
             graphics: Default::default(),
-            damage: Default::default(),
         }
     }
 
@@ -155,7 +264,7 @@ impl<B: Backend + 'static> Catacomb<B> {
         match display.dispatch(Duration::from_millis(0), self) {
             Ok(_) => Ok(PostAction::Continue),
             Err(error) => {
-                eprintln!("I/O error on the Wayland display: {error}");
+                error!("I/O error on the Wayland display: {error}");
                 self.terminated = true;
                 Err(error)
             },
@@ -166,8 +275,19 @@ impl<B: Backend + 'static> Catacomb<B> {
 impl<B> Catacomb<B> {
     /// Handle everything necessary to draw a single frame.
     pub fn create_frame<R: Render>(&mut self, mut renderer: R) {
+        // Pause rendering and frame callbacks until a display is connected again.
+        if !self.output.active() {
+            self.fail_captures("internal output is disconnected");
+            return;
+        }
+
         // Update transaction before rendering to update device orientation.
-        self.windows.update_transaction();
+        self.windows.update_transaction(&self.output);
+
+        // Notify IPC subscribers about committed changes.
+        for event in self.windows.take_events() {
+            self.ipc_subscribers.broadcast(&event);
+        }
 
         // Update surface focus.
         let focus = self.windows.focus();
@@ -177,27 +297,44 @@ impl<B> Catacomb<B> {
         }
 
         // Redraw only when there is damage present.
-        if self.windows.damaged() || self.touch_debug {
-            let _ = renderer.render(self, Catacomb::draw);
-        } else {
+        //
+        // No frames are scheduled while blanked, the backend restarts rendering
+        // once the output is powered on again.
+        let blanked = self.switches.active(SwitchAction::Blank);
+        let damaged = self.windows.damaged()
+            || self.touch_debug
+            || self.back_indicator_damaged()
+            || (!self.docked && self.cursor.damaged())
+            || !self.pending_captures.is_empty();
+        if !blanked && damaged {
+            if let Err(err) = renderer.render(self, Catacomb::draw) {
+                warn!("Rendering failed: {err}");
+            }
+        } else if !blanked {
             renderer.reschedule();
         }
 
         // Handle window liveliness changes.
         self.windows.refresh(&mut self.output);
 
-        // Request new frames for visible windows.
-        self.windows.request_frames();
+        // Request new frames for visible windows, unless the output is blanked.
+        if !blanked {
+            self.windows.request_frames();
+        }
+        self.cursor.request_frame(self.windows.runtime());
     }
 
     /// Draw the current compositor state.
     pub fn draw(&mut self, renderer: &mut Gles2Renderer, frame: &mut Gles2Frame, buffer_age: u8) {
         // Collect pending damage.
-        let max_age = MAX_DAMAGE_AGE as u8;
+        let max_age = self.damage.max_age() as u8;
         let damage = if buffer_age == 0
             || buffer_age > max_age
             || self.windows.fully_damaged()
             || self.touch_debug
+            || self.back_indicator.is_some()
+            || self.back_indicator_damaged()
+            || (!self.docked && self.cursor.damaged())
         {
             let output_size = self.output.size().to_f64().to_physical(self.output.scale());
             self.damage.push(Rectangle::from_loc_and_size((0., 0.), output_size));
@@ -218,21 +355,502 @@ impl<B> Catacomb<B> {
             touch_debug.draw_at(frame, &self.output, rect, 1., None);
         }
 
-        self.windows.draw(renderer, frame, &mut self.graphics, &self.output, damage);
+        let (graphics, config) = (&mut self.graphics, &self.config);
+        self.windows.draw(renderer, frame, graphics, &self.output, config, damage);
+
+        // Render indicator for an active back gesture.
+        self.back_indicator = self.touch_state.back_indicator(&self.output, &self.config.gestures);
+        if let Some(bounds) = self.back_indicator {
+            let _ = renderer.with_context(|_, gl| unsafe {
+                gl.BlendFunc(ffi::SRC_ALPHA, ffi::ONE_MINUS_SRC_ALPHA);
+            });
+
+            let scale = cmp::max(bounds.size.w, bounds.size.h) as f64;
+            let indicator = self.graphics.back_indicator(renderer, &self.config.colors);
+            indicator.draw_at(frame, &self.output, bounds, scale, None);
+
+            let _ = renderer.with_context(|_, gl| unsafe {
+                gl.BlendFunc(ffi::ONE, ffi::ONE_MINUS_SRC_ALPHA);
+            });
+        }
+
+        // The cursor is drawn on the desktop while docked.
+        if !self.docked {
+            self.cursor.draw(renderer, frame, &self.output, damage);
+        }
+
+        // Write the finished frame for all pending captures.
+        if !self.pending_captures.is_empty() {
+            self.write_captures(renderer);
+        }
+
+        // Copy the finished frame for all mirroring outputs.
+        if self.mirroring && !self.external_outputs.is_empty() {
+            // Frames are rendered with the windows' orientation, not the output's.
+            let size = self.output.physical_resolution();
+            let transform = self.windows.orientation().transform();
+            let texture = &mut self.mirror_texture;
+            if let Err(err) = Texture::copy_framebuffer(renderer, texture, size, transform) {
+                error!("Unable to copy frame for mirroring: {err}");
+                self.mirror_texture = None;
+            }
+
+            for external in &mut self.external_outputs {
+                external.dirty = true;
+            }
+        }
+    }
+
+    /// Check if the back gesture indicator changed since the last redraw.
+    fn back_indicator_damaged(&self) -> bool {
+        self.touch_state.back_indicator(&self.output, &self.config.gestures) != self.back_indicator
+    }
+
+    /// Write the next rendered frame to a PNG file.
+    ///
+    /// The callback is invoked with the result once the file was written.
+    pub fn capture_frame(
+        &mut self,
+        path: PathBuf,
+        callback: impl FnOnce(Result<(), String>) + 'static,
+    ) {
+        // Blanked outputs are not rendered.
+        if self.switches.active(SwitchAction::Blank) {
+            callback(Err(String::from("output is blanked")));
+            return;
+        } else if !self.output.active() {
+            callback(Err(String::from("internal output is disconnected")));
+            return;
+        }
+
+        self.pending_captures.push(PendingCapture { path, callback: Box::new(callback) });
+    }
+
+    /// Report all pending captures as failed.
+    pub fn fail_captures(&mut self, reason: &str) {
+        for pending in mem::take(&mut self.pending_captures) {
+            (pending.callback)(Err(reason.into()));
+        }
+    }
+
+    /// Write the currently bound framebuffer to all pending capture files.
+    fn write_captures(&mut self, renderer: &mut Gles2Renderer) {
+        let captures = mem::take(&mut self.pending_captures);
+
+        let capture = match Capture::read(renderer, self.output.physical_resolution()) {
+            Ok(capture) => capture,
+            Err(err) => {
+                error!("Capture error: unable to read framebuffer: {err}");
+                for pending in captures {
+                    (pending.callback)(Err(format!("unable to read framebuffer: {err}")));
+                }
+                return;
+            },
+        };
+
+        for PendingCapture { path, callback } in captures {
+            match capture.write_png(&path) {
+                Ok(()) => callback(Ok(())),
+                Err(err) => {
+                    error!("Capture error: unable to write {path:?}: {err}");
+                    callback(Err(format!("unable to write {path:?}: {err}")));
+                },
+            }
+        }
+    }
+
+    /// Replace the internal output, moving all windows to it.
+    pub fn replace_internal_output(&mut self, mut output: Output) {
+        info!("Using {} as internal output", output.name());
+
+        output.set_orientation(self.output.orientation());
+        output.exclusive = self.output.exclusive;
+        self.output = output;
+
+        // Reconfigure all windows atomically for the new output size.
+        self.windows.resize_all(&mut self.output);
+    }
+
+    /// Stop using the internal output after its display was disconnected.
+    ///
+    /// The output is kept, so windows remain configured for its size, but it
+    /// is no longer rendered and no frames are requested from clients until
+    /// it is replaced by a new internal output.
+    pub fn deactivate_internal_output(&mut self) {
+        info!("Internal output {} disconnected, waiting for a new one", self.output.name());
+        self.output.deactivate();
+    }
+
+    /// Turn an external output into the internal output.
+    pub fn promote_external_output(&mut self, name: &str) {
+        let index = self.external_outputs.iter().position(|ext| ext.output.name() == name);
+        if let Some(index) = index {
+            let external = self.external_outputs.remove(index);
+            self.replace_internal_output(external.output);
+            self.update_docked();
+        }
+    }
+
+    /// Start driving an additional output.
+    pub fn add_external_output(&mut self, output: Output) {
+        info!("Adding external output {}", output.name());
+
+        let max_damage_age = self.config.output.max_damage_age;
+        self.external_outputs.push(ExternalOutput::new(output, max_damage_age));
+        self.update_docked();
+    }
+
+    /// Stop driving an additional output.
+    pub fn remove_external_output(&mut self, name: &str) {
+        info!("Removing external output {name}");
+
+        self.external_outputs.retain(|external| external.output.name() != name);
+        self.update_docked();
+    }
+
+    /// Find an external output by its name.
+    pub fn external_output(&self, name: &str) -> Option<&Output> {
+        self.external_outputs.iter().map(|external| &external.output).find(|o| o.name() == name)
+    }
+
+    /// Find any output by its name.
+    pub fn output_by_name(&self, name: &str) -> Option<&Output> {
+        if self.output.name() == name {
+            Some(&self.output)
+        } else {
+            self.external_output(name)
+        }
+    }
+
+    /// Mode requested for an output.
+    pub fn output_mode(&self, name: &str) -> Option<OutputMode> {
+        self.mode_overrides.get(name).or_else(|| self.config.modes.get(name)).copied()
+    }
+
+    /// Update an output's mode after the backend switched to it.
+    pub fn set_output_mode(&mut self, name: &str, mode: WaylandMode) {
+        if self.output.name() == name {
+            self.output.set_mode(mode);
+
+            // Reconfigure all windows atomically for the new output size.
+            self.windows.resize_all(&mut self.output);
+        } else if let Some(external) =
+            self.external_outputs.iter_mut().find(|ext| ext.output.name() == name)
+        {
+            external.output.set_mode(mode);
+            external.damage_all();
+
+            // Retile the desktop for the new output size.
+            self.update_docked();
+        }
+    }
+
+    /// Output used for the docked desktop.
+    pub fn desktop_output(&self) -> Option<&Output> {
+        self.external_outputs.first().map(|external| &external.output).filter(|_| self.docked)
+    }
+
+    /// Check if windows are tiled on an external output.
+    pub fn docked(&self) -> bool {
+        self.docked
+    }
+
+    /// Track an external keyboard or pointer device.
+    pub fn add_peripheral(&mut self, id: String) {
+        if !self.peripherals.contains(&id) {
+            self.peripherals.push(id);
+            self.update_docked();
+        }
+    }
+
+    /// Stop tracking an external keyboard or pointer device.
+    pub fn remove_peripheral(&mut self, id: &str) {
+        self.peripherals.retain(|peripheral| peripheral != id);
+        self.update_docked();
+    }
+
+    /// Switch between the docked and mobile layout.
+    ///
+    /// The docked layout is used while both an external output and an
+    /// external keyboard or pointer are connected.
+    fn update_docked(&mut self) {
+        let docked = !self.peripherals.is_empty() && !self.external_outputs.is_empty();
+        let desktop = self.external_outputs.first().map(|external| &external.output);
+
+        match desktop {
+            Some(desktop) if docked => {
+                if !self.docked {
+                    info!("Docked, using {} as desktop", desktop.name());
+                }
+                self.windows.dock(desktop);
+            },
+            _ if self.docked => {
+                info!("Undocked, moving all windows to the internal output");
+                self.windows.undock(&mut self.output, desktop);
+            },
+            _ => return,
+        }
+        self.docked = docked;
+
+        for external in &mut self.external_outputs {
+            external.damage_all();
+        }
+    }
+
+    /// Enable or disable mirroring of the internal output.
+    pub fn set_mirroring(&mut self, mirroring: bool) {
+        if mirroring == self.mirroring {
+            return;
+        }
+
+        info!("{} output mirroring", if mirroring { "Enabling" } else { "Disabling" });
+
+        self.mirroring = mirroring;
+        self.mirror_texture = None;
+
+        // Redraw the internal output to update the mirrored frame.
+        self.windows.damage_all();
+
+        for external in &mut self.external_outputs {
+            external.damage_all();
+        }
+    }
+
+    /// Handle everything necessary to draw a frame for an external output.
+    ///
+    /// Windows and the mirrored internal output can only be drawn on outputs
+    /// which share the internal output's renderer, as indicated by
+    /// `shared_renderer`.
+    pub fn create_external_frame<R: Render>(
+        &mut self,
+        name: &str,
+        shared_renderer: bool,
+        mut renderer: R,
+    ) {
+        let index = self.external_outputs.iter().position(|ext| ext.output.name() == name);
+        let dirty = match index {
+            Some(index) => {
+                let desktop_damaged = self.windows.desktop_damaged() || self.cursor.damaged();
+                self.external_outputs[index].dirty || (index == 0 && self.docked && desktop_damaged)
+            },
+            None => false,
+        };
+
+        // Redraw only when the output changed.
+        if !dirty {
+            renderer.reschedule();
+            return;
+        }
+
+        let result = renderer.render(self, |catacomb, renderer, frame, buffer_age| {
+            catacomb.draw_external(name, shared_renderer, renderer, frame, buffer_age);
+        });
+        if let Err(err) = result {
+            warn!("Rendering {name} failed: {err}");
+        }
+    }
+
+    /// Draw the current state of an external output.
+    fn draw_external(
+        &mut self,
+        name: &str,
+        shared_renderer: bool,
+        renderer: &mut Gles2Renderer,
+        frame: &mut Gles2Frame,
+        buffer_age: u8,
+    ) {
+        let index = self.external_outputs.iter().position(|ext| ext.output.name() == name);
+        let index = match index {
+            Some(index) => index,
+            None => return,
+        };
+        let external = &mut self.external_outputs[index];
+        external.dirty = false;
+
+        // Scale the internal frame to fit, keeping its aspect ratio.
+        let mirror = self.mirror_texture.as_mut().filter(|_| self.mirroring && shared_renderer);
+        let mirror_bounds = mirror.as_ref().map(|texture| mirror_bounds(&external.output, texture));
+
+        // Without layout changes, only the mirrored frame needs to be redrawn.
+        let max_age = external.damage.max_age() as u8;
+        let damage = match mirror_bounds {
+            Some((bounds, _))
+                if buffer_age > 0 && buffer_age <= max_age && !external.fully_damaged =>
+            {
+                let bounds = bounds.to_f64().to_physical(external.output.scale());
+                external.damage.push(bounds);
+                external.damage.take_since(buffer_age)
+            },
+            _ => {
+                let output_size = external.output.physical_resolution().to_f64();
+                external.damage.push(Rectangle::from_loc_and_size((0., 0.), output_size));
+                external.damage.take_since(1)
+            },
+        };
+        external.fully_damaged = false;
+
+        let _ = frame.clear([0., 0., 0., 1.], damage);
+
+        if !shared_renderer {
+            // Log only once, since this is hit for every redraw.
+            let unsupported = self.mirroring || (self.docked && index == 0);
+            if unsupported && !mem::replace(&mut external.unshared_logged, true) {
+                warn!(
+                    "Unable to draw on {name}: output is not driven by the internal output's GPU"
+                );
+            }
+            return;
+        }
+
+        // Draw the mirrored frame, or the desktop if nothing is mirrored.
+        match (mirror, mirror_bounds) {
+            (Some(texture), Some((bounds, scale))) => {
+                texture.draw_at(frame, &external.output, bounds, scale, None);
+            },
+            _ if !self.mirroring && self.docked && index == 0 => {
+                self.windows.draw_desktop(renderer, frame, &external.output, damage);
+                self.cursor.draw(renderer, frame, &external.output, damage);
+            },
+            _ => (),
+        }
+    }
+
+    /// Apply a new configuration.
+    ///
+    /// Returns `true` if output modes need to be reloaded by the backend.
+    pub fn reload_config(&mut self, config: Config) -> bool {
+        if config == self.config {
+            return false;
+        }
+
+        // Discard damage history when the tracked buffer ages change.
+        if config.output.max_damage_age != self.damage.max_age() {
+            self.damage = Damage::new(config.output.max_damage_age);
+        }
+
+        // Reconfigure all windows atomically after scale changes.
+        if config.output.scale != self.output.scale() {
+            self.output.set_scale(config.output.scale);
+            self.windows.resize_all(&mut self.output);
+        }
+
+        self.set_mirroring(config.output.mirror);
+
+        // Switch outputs to their new preferred modes.
+        let modes_changed = config.modes != self.config.modes;
+
+        // Redraw external outputs with the new settings.
+        for external in &mut self.external_outputs {
+            external.damage = Damage::new(config.output.max_damage_age);
+            external.output.set_scale(config.output.scale);
+            external.damage_all();
+        }
+        self.update_docked();
+
+        self.windows.damage_all();
+        self.config = config;
+
+        modes_changed
+    }
+
+    /// Toggle the touch position indicator.
+    pub fn toggle_touch_debug(&mut self) {
+        self.touch_debug = !self.touch_debug;
+
+        // Clear the indicator from the screen.
+        self.windows.damage_all();
     }
 
     /// Focus a new surface.
     pub fn focus(&mut self, surface: Option<&WlSurface>) {
         self.virtual_keyboard.set_focus(surface, SERIAL_COUNTER.next_serial());
         self.keyboard.set_focus(surface, SERIAL_COUNTER.next_serial());
-        self.text_input.set_focus(surface, None);
+
+        // Hide the on-screen keyboard while it's disabled by a switch.
+        let text_input_focus = surface.filter(|_| !self.switches.active(SwitchAction::DisableOsk));
+        self.text_input.set_focus(text_input_focus, None);
     }
 }
 
+/// Output driven next to the internal one.
+pub struct ExternalOutput {
+    pub output: Output,
+    damage: Damage,
+
+    /// Output requires a redraw.
+    dirty: bool,
+
+    /// Output requires a redraw without using its damage history.
+    fully_damaged: bool,
+
+    /// Missing renderer sharing with the internal output was reported.
+    unshared_logged: bool,
+}
+
+impl ExternalOutput {
+    fn new(output: Output, max_damage_age: usize) -> Self {
+        Self {
+            output,
+            damage: Damage::new(max_damage_age),
+            fully_damaged: true,
+            dirty: true,
+            unshared_logged: false,
+        }
+    }
+
+    /// Redraw the entire output with the next frame.
+    fn damage_all(&mut self) {
+        self.fully_damaged = true;
+        self.dirty = true;
+    }
+}
+
+/// Bounds of the mirrored internal frame on an external output.
+///
+/// Returns the bounds together with the scale applied to the frame.
+fn mirror_bounds(output: &Output, texture: &Texture) -> (Rectangle<i32, Logical>, f64) {
+    let output_size = output.size();
+    let texture_size = texture.size();
+    let scale = (output_size.w as f64 / texture_size.w as f64)
+        .min(output_size.h as f64 / texture_size.h as f64);
+    let size = texture_size.scale(scale);
+    let loc = ((output_size.w - size.w) / 2, (output_size.h - size.h) / 2);
+    (Rectangle::from_loc_and_size(loc, size), scale)
+}
+
+/// Frame capture waiting for the next rendered frame.
+pub struct PendingCapture {
+    path: PathBuf,
+    callback: Box<dyn FnOnce(Result<(), String>)>,
+}
+
+/// Compositor startup parameters.
+#[derive(Default, Debug)]
+pub struct Startup {
+    /// Wayland socket name, picked automatically if not specified.
+    pub socket: Option<String>,
+    /// Command spawned once the Wayland socket is ready.
+    pub command: Vec<String>,
+}
+
 /// Backend capabilities.
 pub trait Backend {
     fn seat_name(&self) -> String;
     fn change_vt(&mut self, _vt: i32) {}
+
+    /// Switch all outputs to their currently requested modes.
+    fn reload_modes(&mut self) {}
+
+    /// Check if an output can be switched to a mode.
+    ///
+    /// Modes of disconnected outputs are accepted, since they're only applied
+    /// once the output is connected.
+    fn check_mode(&self, _output: &str, _mode: Option<OutputMode>) -> Result<(), String> {
+        Err(String::from("output modes can't be changed with this backend"))
+    }
+
+    /// Turn the internal output's display on or off.
+    fn set_internal_power(&mut self, _on: bool) {}
 }
 
 /// Abstraction over backend-specific rendering.
@@ -253,16 +871,16 @@ pub trait Render {
     fn reschedule(&mut self) {}
 }
 
-#[derive(Default, Debug)]
+#[derive(Debug)]
 pub struct Damage {
     /// Combined damage history for all tracked buffer ages.
     damage: Vec<Rectangle<f64, Physical>>,
 
     /// Tracked damage rectangles per buffer age.
     ///
-    /// This must be one bigger than [`MAX_DAMAGE_AGE`], since the last slot is
-    /// reserved for pending damage for the next frame.
-    rects: [usize; MAX_DAMAGE_AGE + 1],
+    /// This must be one bigger than the maximum buffer age, since the last slot
+    /// is reserved for pending damage for the next frame.
+    rects: Vec<usize>,
 
     /// Buffer for storing current damage.
     ///
@@ -272,6 +890,16 @@ pub struct Damage {
 }
 
 impl Damage {
+    /// Create damage tracking for buffers up to `max_age`.
+    pub fn new(max_age: usize) -> Self {
+        Self { rects: vec![0; max_age + 1], damage: Vec::new(), current: Vec::new() }
+    }
+
+    /// Maximum buffer age before damage information is discarded.
+    pub fn max_age(&self) -> usize {
+        self.rects.len() - 1
+    }
+
     /// Add pending damage for the next frame.
     pub fn push(&mut self, damage: Rectangle<f64, Physical>) {
         self.rects[self.rects.len() - 1] += 1;
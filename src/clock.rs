@@ -0,0 +1,47 @@
+//! Monotonic time source.
+//!
+//! Tests replace the system clock with a fake clock that only moves forward
+//! when it is explicitly advanced.
+
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+pub use fake::advance;
+
+/// Current point in time.
+#[cfg(not(test))]
+pub fn now() -> Instant {
+    Instant::now()
+}
+
+/// Current point in time.
+#[cfg(test)]
+pub fn now() -> Instant {
+    fake::now()
+}
+
+/// Time passed since an earlier point in time.
+pub fn elapsed(since: Instant) -> Duration {
+    now().saturating_duration_since(since)
+}
+
+#[cfg(test)]
+mod fake {
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+
+    thread_local! {
+        static START: Instant = Instant::now();
+        static OFFSET: Cell<Duration> = Cell::new(Duration::ZERO);
+    }
+
+    /// Current fake time.
+    pub fn now() -> Instant {
+        START.with(|start| *start) + OFFSET.with(Cell::get)
+    }
+
+    /// Move the fake clock forward.
+    pub fn advance(duration: Duration) {
+        OFFSET.with(|offset| offset.set(offset.get() + duration));
+    }
+}
@@ -1,4 +1,1069 @@
-//! Compile-time configuration file.
+//! Runtime configuration.
 
-/// Application used as application drawer.
-pub const APP_DRAWER: &str = "tzompantli";
+use std::collections::HashMap;
+use std::ffi::{CString, OsString};
+use std::fmt::{self, Formatter};
+use std::io::{self, ErrorKind};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::{env, fs, mem, ptr};
+
+use calloop::generic::Generic;
+use calloop::{Interest, LoopHandle, Mode, PostAction};
+use catacomb_ipc::OutputMode;
+use log::warn;
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::Deserialize;
+use toml::value::{Table, Value};
+
+use crate::catacomb::{Backend, Catacomb};
+
+/// Configuration file location relative to the XDG config directory.
+const CONFIG_PATH: &str = "catacomb/catacomb.toml";
+
+/// Catacomb configuration.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    pub launcher: LauncherConfig,
+    pub gestures: GestureConfig,
+    pub touchpad: TouchpadConfig,
+    pub switches: SwitchConfig,
+    pub overview: OverviewConfig,
+    pub colors: ColorConfig,
+    pub output: OutputConfig,
+    pub headless: HeadlessConfig,
+    /// Preferred modes by output name.
+    pub modes: HashMap<String, OutputMode>,
+    /// Per-device input settings.
+    pub devices: Vec<DeviceConfig>,
+
+    /// Location of the configuration file.
+    pub path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Load the configuration file.
+    ///
+    /// If the configuration file does not exist or cannot be read, the default
+    /// configuration is used instead.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let path = match path {
+            Some(path) => path,
+            None => return Self::default(),
+        };
+
+        let mut config = match fs::read_to_string(&path) {
+            Ok(content) => Self::from_toml(&content),
+            Err(err) if err.kind() == ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                warn!("Config error: unable to read {path:?}: {err}");
+                Self::default()
+            },
+        };
+        config.path = Some(path);
+
+        config
+    }
+
+    /// Parse configuration from its TOML representation.
+    ///
+    /// Invalid keys or values are reported and replaced by their defaults,
+    /// without affecting any of the other settings.
+    pub fn from_toml(content: &str) -> Self {
+        let mut config = Self::default();
+
+        let table = match content.parse::<Value>() {
+            Ok(Value::Table(table)) => table,
+            Ok(_) => return config,
+            Err(err) => {
+                warn!("Config error: {err}");
+                return config;
+            },
+        };
+
+        for (key, value) in table {
+            match key.as_str() {
+                "launcher" => config.launcher = parse_section(&key, value),
+                "gestures" => config.gestures = parse_section(&key, value),
+                "touchpad" => config.touchpad = parse_section(&key, value),
+                "switches" => config.switches = parse_section(&key, value),
+                "overview" => config.overview = parse_section(&key, value),
+                "colors" => config.colors = parse_section(&key, value),
+                "output" => config.output = parse_section(&key, value),
+                "headless" => config.headless = parse_section(&key, value),
+                "modes" => config.modes = parse_section(&key, value),
+                "devices" => config.devices = parse_section(&key, value),
+                _ => warn!("Config error: unknown section `{key}`"),
+            }
+        }
+
+        config.validate();
+
+        config
+    }
+
+    /// Replace values which would put the compositor into an invalid state.
+    fn validate(&mut self) {
+        if !(self.output.scale > 0.) {
+            warn!("Config error: [output] scale must be positive");
+            self.output.scale = OutputConfig::default().scale;
+        }
+
+        for binding in self.gestures.bindings_mut() {
+            if binding.fingers < 2 && binding.action != GestureAction::None {
+                warn!("Config error: [gestures] bindings require at least 2 fingers");
+                binding.action = GestureAction::None;
+            }
+        }
+
+        if !(0. ..=1.).contains(&self.gestures.friction) {
+            warn!("Config error: [gestures] friction must be between 0 and 1");
+            self.gestures.friction = GestureConfig::default().friction;
+        }
+
+        if !(0. ..0.5).contains(&self.gestures.edge_inset_percentage) {
+            warn!("Config error: [gestures] edge_inset_percentage must be between 0 and 0.5");
+            self.gestures.edge_inset_percentage = GestureConfig::default().edge_inset_percentage;
+        }
+
+        let overview = &mut self.overview;
+        let default_overview = OverviewConfig::default();
+        if !(overview.foreground_percentage > 0. && overview.foreground_percentage <= 1.) {
+            warn!("Config error: [overview] foreground_percentage must be between 0 and 1");
+            overview.foreground_percentage = default_overview.foreground_percentage;
+        }
+        if !(0. ..=1.).contains(&overview.background_percentage) {
+            warn!("Config error: [overview] background_percentage must be between 0 and 1");
+            overview.background_percentage = default_overview.background_percentage;
+        }
+        if !(0. ..=0.5).contains(&overview.drag_and_drop_percentage) {
+            warn!("Config error: [overview] drag_and_drop_percentage must be between 0 and 0.5");
+            overview.drag_and_drop_percentage = default_overview.drag_and_drop_percentage;
+        }
+        if !(overview.close_distance > 0. && overview.close_distance <= 1.) {
+            warn!("Config error: [overview] close_distance must be between 0 and 1");
+            overview.close_distance = default_overview.close_distance;
+        }
+
+        if self.touchpad.fingers < 2 {
+            warn!("Config error: [touchpad] fingers must be at least 2");
+            self.touchpad.fingers = TouchpadConfig::default().fingers;
+        }
+
+        for device in &mut self.devices {
+            if device.rotation % 90 != 0 {
+                warn!("Config error: [[devices]] rotation must be a multiple of 90");
+                device.rotation = 0;
+            }
+        }
+
+        if self.output.max_damage_age == 0 {
+            warn!("Config error: [output] max_damage_age must be at least 1");
+            self.output.max_damage_age = OutputConfig::default().max_damage_age;
+        }
+
+        if self.headless.width <= 0 || self.headless.height <= 0 || self.headless.refresh == 0 {
+            warn!("Config error: [headless] mode must not be empty");
+            self.headless = HeadlessConfig::default();
+        }
+    }
+}
+
+/// Default configuration file path.
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join(CONFIG_PATH))
+}
+
+/// Reload the configuration whenever its file is modified.
+pub fn watch<B: Backend + 'static>(loop_handle: LoopHandle<'_, Catacomb<B>>, path: PathBuf) {
+    let watcher = match FileWatcher::new(&path) {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            warn!("Config error: unable to watch {path:?}: {err}");
+            return;
+        },
+    };
+
+    let source = Generic::new(watcher, Interest::READ, Mode::Level);
+    loop_handle
+        .insert_source(source, move |_, watcher, catacomb| {
+            if watcher.read_events()? {
+                let modes_changed = catacomb.reload_config(Config::load(Some(path.clone())));
+
+                // Apply new switch bindings and modes.
+                catacomb.update_switch_actions();
+                if modes_changed {
+                    catacomb.backend.reload_modes();
+                }
+            }
+
+            Ok(PostAction::Continue)
+        })
+        .expect("insert config watcher");
+}
+
+/// Inotify watch for changes to a single file.
+///
+/// The file's parent directory is watched, to also catch editors which save
+/// files by renaming a new file over the old one.
+struct FileWatcher {
+    fd: RawFd,
+    file_name: OsString,
+}
+
+impl FileWatcher {
+    fn new(path: &Path) -> io::Result<Self> {
+        let file_name = path.file_name().ok_or(ErrorKind::InvalidInput)?.to_os_string();
+        let directory = match path.parent() {
+            Some(directory) if !directory.as_os_str().is_empty() => directory,
+            _ => Path::new("."),
+        };
+        let directory = CString::new(directory.as_os_str().as_bytes())?;
+
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let watcher = Self { fd, file_name };
+
+        let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_MOVED_FROM | libc::IN_DELETE;
+        if unsafe { libc::inotify_add_watch(fd, directory.as_ptr(), mask) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(watcher)
+    }
+
+    /// Consume all pending events.
+    ///
+    /// Returns `true` if any of the events affected the watched file.
+    fn read_events(&mut self) -> io::Result<bool> {
+        let mut buffer = [0u8; 4096];
+        let mut changed = false;
+
+        loop {
+            let read = unsafe { libc::read(self.fd, buffer.as_mut_ptr().cast(), buffer.len()) };
+            if read < 0 {
+                let err = io::Error::last_os_error();
+                return match err.kind() {
+                    ErrorKind::WouldBlock => Ok(changed),
+                    _ => Err(err),
+                };
+            }
+
+            // Events consist of a fixed-size header followed by a NUL-padded name.
+            let mut offset = 0;
+            while offset + mem::size_of::<libc::inotify_event>() <= read as usize {
+                let event: libc::inotify_event =
+                    unsafe { ptr::read_unaligned(buffer[offset..].as_ptr().cast()) };
+                let name_start = offset + mem::size_of::<libc::inotify_event>();
+                offset = name_start + event.len as usize;
+
+                let name = buffer[name_start..offset].split(|byte| *byte == 0).next();
+                changed |= name == Some(self.file_name.as_bytes());
+            }
+        }
+    }
+}
+
+impl AsRawFd for FileWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+/// Deserialize a configuration section, replacing invalid fields by defaults.
+fn parse_section<T: DeserializeOwned + Default>(name: &str, value: Value) -> T {
+    valid_fields::<T>(name, value, &|value| value)
+        .and_then(|value| value.try_into().ok())
+        .unwrap_or_default()
+}
+
+/// Remove all fields which prevent a section from being deserialized.
+///
+/// The `section` function places a value at its position in the section, so
+/// every field is checked together with the fields accepted before it. Returns
+/// `None` if the value cannot be used at all.
+fn valid_fields<T: DeserializeOwned>(
+    path: &str,
+    value: Value,
+    section: &dyn Fn(Value) -> Value,
+) -> Option<Value> {
+    let err = match section(value.clone()).try_into::<T>() {
+        Ok(_) => return Some(value),
+        Err(err) => err,
+    };
+
+    // Tables and arrays of tables are filtered if they're valid without content.
+    let accepts_empty = |empty: Value| section(empty).try_into::<T>().is_ok();
+    match value {
+        Value::Table(fields) if accepts_empty(Value::Table(Table::new())) => {
+            let mut valid = Table::new();
+            for (key, field) in fields {
+                let with_field = |field: Value| {
+                    let mut table = valid.clone();
+                    table.insert(key.clone(), field);
+                    section(Value::Table(table))
+                };
+                let field_path = format!("{path}.{key}");
+                if let Some(field) = valid_fields::<T>(&field_path, field, &with_field) {
+                    valid.insert(key, field);
+                }
+            }
+            Some(Value::Table(valid))
+        },
+        Value::Array(items)
+            if items.iter().all(Value::is_table) && accepts_empty(Value::Array(Vec::new())) =>
+        {
+            let mut valid = Vec::new();
+            for (i, item) in items.into_iter().enumerate() {
+                let with_item = |item: Value| {
+                    let mut array = valid.clone();
+                    array.push(item);
+                    section(Value::Array(array))
+                };
+                let item_path = format!("{path}[{i}]");
+                if let Some(item) = valid_fields::<T>(&item_path, item, &with_item) {
+                    valid.push(item);
+                }
+            }
+            Some(Value::Array(valid))
+        },
+        _ => {
+            warn!("Config error in `{path}`: {err}; using default");
+            None
+        },
+    }
+}
+
+/// Application drawer.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LauncherConfig {
+    /// Command started by the power button.
+    pub command: String,
+    /// Arguments passed to the command.
+    pub args: Vec<String>,
+}
+
+impl Default for LauncherConfig {
+    fn default() -> Self {
+        Self { command: String::from("tzompantli"), args: Vec::new() }
+    }
+}
+
+/// Touchscreen gestures.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GestureConfig {
+    /// Size of the corner gesture zones in pixels at scale 1.
+    #[serde(alias = "overview_accuracy")]
+    pub corner_size: f64,
+    /// Width of the edge gesture zones in pixels at scale 1.
+    #[serde(alias = "home_accuracy")]
+    pub edge_size: f64,
+    /// Percentage of each edge's length excluded from its zone at both ends.
+    #[serde(alias = "home_width_percentage")]
+    pub edge_inset_percentage: f64,
+
+    /// Edge swipe bindings.
+    pub edges: EdgeBindings,
+    /// Key sent to the focused window by the back action.
+    pub back_key: BackKey,
+
+    /// Fraction of the fling velocity lost every 16ms.
+    pub friction: f64,
+    /// Minimum touch release velocity in logical pixels per second for flings.
+    pub min_fling_velocity: f64,
+
+    /// Multi-finger gesture bindings.
+    ///
+    /// Two-finger swipes require a longer distance, so shorter pans still reach
+    /// the window below.
+    pub swipe_up: GestureBinding,
+    pub swipe_down: GestureBinding,
+    pub swipe_left: GestureBinding,
+    pub swipe_right: GestureBinding,
+    pub pinch_in: GestureBinding,
+    pub pinch_out: GestureBinding,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            corner_size: 60.,
+            edge_size: 30.,
+            edge_inset_percentage: 0.25,
+            edges: EdgeBindings::default(),
+            back_key: BackKey::Escape,
+            friction: 0.1,
+            min_fling_velocity: 100.,
+            swipe_up: GestureBinding::new(3, GestureAction::Overview),
+            swipe_down: GestureBinding::default(),
+            swipe_left: GestureBinding::new(2, GestureAction::Next),
+            swipe_right: GestureBinding::new(2, GestureAction::Previous),
+            pinch_in: GestureBinding::new(3, GestureAction::Home),
+            pinch_out: GestureBinding::default(),
+        }
+    }
+}
+
+impl GestureConfig {
+    /// All multi-finger gesture bindings.
+    fn bindings_mut(&mut self) -> [&mut GestureBinding; 6] {
+        [
+            &mut self.swipe_up,
+            &mut self.swipe_down,
+            &mut self.swipe_left,
+            &mut self.swipe_right,
+            &mut self.pinch_in,
+            &mut self.pinch_out,
+        ]
+    }
+}
+
+/// Swipes starting at the output's edges and corners.
+///
+/// Edges are relative to the current orientation, so `bottom` is always the
+/// edge closest to the user.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EdgeBindings {
+    pub top: GestureAction,
+    pub bottom: GestureAction,
+    pub left: GestureAction,
+    pub right: GestureAction,
+    pub top_left: GestureAction,
+    pub top_right: GestureAction,
+    pub bottom_left: GestureAction,
+    pub bottom_right: GestureAction,
+}
+
+impl Default for EdgeBindings {
+    fn default() -> Self {
+        Self {
+            top: GestureAction::None,
+            bottom: GestureAction::Home,
+            left: GestureAction::Back,
+            right: GestureAction::None,
+            top_left: GestureAction::None,
+            top_right: GestureAction::None,
+            bottom_left: GestureAction::None,
+            bottom_right: GestureAction::Overview,
+        }
+    }
+}
+
+/// Multi-finger touchscreen gesture.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GestureBinding {
+    /// Number of fingers required for the gesture.
+    pub fingers: u32,
+    /// Action triggered once the gesture is recognized.
+    pub action: GestureAction,
+}
+
+impl Default for GestureBinding {
+    fn default() -> Self {
+        Self::new(3, GestureAction::None)
+    }
+}
+
+impl GestureBinding {
+    pub fn new(fingers: u32, action: GestureAction) -> Self {
+        Self { fingers, action }
+    }
+}
+
+/// Touchpad gesture bindings.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TouchpadConfig {
+    /// Number of fingers used for navigation gestures.
+    pub fingers: u32,
+    /// Scroll through the overview with horizontal swipes.
+    pub scroll_overview: bool,
+    pub swipe_up: GestureAction,
+    pub swipe_down: GestureAction,
+    pub swipe_left: GestureAction,
+    pub swipe_right: GestureAction,
+    pub pinch_in: GestureAction,
+    pub pinch_out: GestureAction,
+}
+
+impl Default for TouchpadConfig {
+    fn default() -> Self {
+        Self {
+            fingers: 3,
+            scroll_overview: true,
+            swipe_up: GestureAction::Overview,
+            swipe_down: GestureAction::Home,
+            swipe_left: GestureAction::None,
+            swipe_right: GestureAction::None,
+            pinch_in: GestureAction::Overview,
+            pinch_out: GestureAction::Home,
+        }
+    }
+}
+
+/// Hardware switch bindings.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SwitchConfig {
+    pub lid: SwitchBinding,
+    pub tablet_mode: SwitchBinding,
+    /// Slide-out keyboard, which is `on` while the keyboard is exposed.
+    pub keypad_slide: SwitchBinding,
+
+    /// Command started by the `lock` action.
+    pub lock_command: Option<String>,
+    /// Arguments passed to the lock command.
+    pub lock_args: Vec<String>,
+}
+
+impl Default for SwitchConfig {
+    fn default() -> Self {
+        Self {
+            lid: SwitchBinding {
+                on: vec![SwitchAction::Blank, SwitchAction::Lock],
+                off: Vec::new(),
+            },
+            tablet_mode: SwitchBinding::default(),
+            keypad_slide: SwitchBinding {
+                on: vec![SwitchAction::DisableOsk, SwitchAction::Landscape],
+                off: Vec::new(),
+            },
+            lock_command: None,
+            lock_args: Vec::new(),
+        }
+    }
+}
+
+/// Actions applied while a switch is in either state.
+///
+/// For the lid switch, `on` means the lid is closed.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SwitchBinding {
+    pub on: Vec<SwitchAction>,
+    pub off: Vec<SwitchAction>,
+}
+
+/// Compositor state triggered by a switch.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SwitchAction {
+    /// Turn the internal output black.
+    Blank,
+    /// Start the `lock_command` when the action is activated.
+    Lock,
+    /// Stop activating the on-screen keyboard for text input.
+    DisableOsk,
+    /// Force landscape orientation.
+    Landscape,
+}
+
+/// Input device settings.
+///
+/// Settings are applied when a device is added, using the first entry matching
+/// it.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceConfig {
+    /// Device name to match.
+    pub name: Option<String>,
+    /// Udev properties the device must have.
+    pub udev: HashMap<String, String>,
+
+    /// Libinput calibration matrix for absolute input.
+    pub calibration_matrix: Option<[f32; 6]>,
+    /// Output which must be the internal output for absolute input to be used.
+    ///
+    /// Absolute input is not mapped to other outputs, so touchscreens of
+    /// external displays are ignored until they become the internal output.
+    pub internal_output: Option<String>,
+    /// Clockwise rotation of absolute input relative to the output in degrees.
+    pub rotation: u32,
+
+    /// Enable tap-to-click on touchpads.
+    pub tap_to_click: Option<bool>,
+    /// Scroll content in the direction of finger movement.
+    pub natural_scroll: Option<bool>,
+    /// Pointer acceleration of mice and touchpads.
+    pub accel_profile: Option<AccelProfile>,
+}
+
+impl DeviceConfig {
+    /// Check if the settings apply to a device.
+    pub fn matches(&self, name: &str, udev_property: impl Fn(&str) -> Option<String>) -> bool {
+        self.name.as_ref().map_or(true, |expected| expected == name)
+            && self.udev.iter().all(|(key, value)| udev_property(key).as_ref() == Some(value))
+    }
+}
+
+/// Pointer acceleration profile.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccelProfile {
+    /// Constant pointer speed.
+    Flat,
+    /// Pointer speed scaled with device speed.
+    Adaptive,
+}
+
+/// Navigation triggered by a gesture.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GestureAction {
+    None,
+    Overview,
+    Home,
+    /// Switch to the next window.
+    Next,
+    /// Switch to the previous window.
+    Previous,
+    /// Send a back navigation to the focused window.
+    Back,
+    /// Start the `[launcher]` command.
+    Launcher,
+    /// Show or hide the secondary window.
+    ToggleSecondary,
+    /// Notify IPC subscribers.
+    Ipc,
+}
+
+/// Key used for back navigation.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackKey {
+    Escape,
+    /// Dedicated `KEY_BACK` navigation key.
+    Back,
+}
+
+impl BackKey {
+    /// Evdev keycode of the key.
+    pub fn keycode(&self) -> u32 {
+        match self {
+            Self::Escape => 1,
+            Self::Back => 158,
+        }
+    }
+}
+
+/// Application overview layout.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverviewConfig {
+    /// Percentage of output width reserved for the main window.
+    pub foreground_percentage: f64,
+    /// Percentage of remaining space reserved for background windows.
+    pub background_percentage: f64,
+    /// Percentage of the screen for the drop highlight areas.
+    pub drag_and_drop_percentage: f64,
+    /// Percentage of the output height a window can be moved before closing.
+    pub close_distance: f64,
+}
+
+impl Default for OverviewConfig {
+    fn default() -> Self {
+        Self {
+            foreground_percentage: 0.75,
+            background_percentage: 0.5,
+            drag_and_drop_percentage: 0.3,
+            close_distance: 0.25,
+        }
+    }
+}
+
+/// Compositor decoration colors.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorConfig {
+    /// Color of the hovered overview tiling location highlight.
+    pub active_drop_target: Color,
+    /// Color of the overview tiling location highlight.
+    pub drop_target: Color,
+    /// Background color behind half-size windows in the overview.
+    pub background: Color,
+    /// Decoration titlebar color in the overview.
+    pub title: Color,
+    /// Decoration border color in the overview.
+    pub border: Color,
+    /// Color of the back gesture indicator.
+    pub back_indicator: Color,
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        Self {
+            active_drop_target: Color([128, 128, 128, 128]),
+            drop_target: Color([128, 128, 128, 64]),
+            background: Color([0, 0, 0, 255]),
+            title: Color([64, 64, 64, 255]),
+            border: Color([32, 32, 32, 255]),
+            back_indicator: Color([128, 128, 128, 128]),
+        }
+    }
+}
+
+/// Output rendering.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Output scale.
+    pub scale: f64,
+    /// Maximum buffer age before damage information is discarded.
+    pub max_damage_age: usize,
+    /// Show the internal output's content on all external outputs.
+    pub mirror: bool,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self { scale: 2., max_damage_age: 2, mirror: false }
+    }
+}
+
+/// Headless backend virtual output.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeadlessConfig {
+    /// Output width in physical pixels.
+    pub width: i32,
+    /// Output height in physical pixels.
+    pub height: i32,
+    /// Refresh rate in Hz.
+    pub refresh: u32,
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> Self {
+        Self { width: 720, height: 1440, refresh: 60 }
+    }
+}
+
+/// RGBA color.
+///
+/// Colors are deserialized from `#RRGGBB` or `#RRGGBBAA` hex strings.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Color(pub [u8; 4]);
+
+impl Color {
+    /// Color as RGBA bytes.
+    pub fn rgba(&self) -> [u8; 4] {
+        self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ColorVisitor;
+
+        impl<'de> Visitor<'de> for ColorVisitor {
+            type Value = Color;
+
+            fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+                f.write_str("hex color like #RRGGBB or #RRGGBBAA")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Color, E> {
+                let hex = value.strip_prefix('#').unwrap_or(value);
+                if (hex.len() != 6 && hex.len() != 8) || !hex.is_ascii() {
+                    return Err(E::invalid_value(de::Unexpected::Str(value), &self));
+                }
+
+                let mut rgba = [255; 4];
+                for (i, channel) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+                    *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+                        .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))?;
+                }
+
+                Ok(Color(rgba))
+            }
+        }
+
+        deserializer.deserialize_str(ColorVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::process;
+
+    use super::*;
+
+    #[test]
+    fn watch_renamed_file() {
+        let directory = env::temp_dir().join(format!("catacomb-config-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("catacomb.toml");
+        fs::write(&path, "").unwrap();
+
+        let mut watcher = FileWatcher::new(&path).unwrap();
+        assert!(!watcher.read_events().unwrap());
+
+        // Unrelated files are ignored.
+        fs::write(directory.join("other.toml"), "").unwrap();
+        assert!(!watcher.read_events().unwrap());
+
+        // Editors may save by renaming a temporary file.
+        let temporary = directory.join("catacomb.toml.tmp");
+        fs::write(&temporary, "[launcher]").unwrap();
+        fs::rename(&temporary, &path).unwrap();
+        assert!(watcher.read_events().unwrap());
+
+        fs::write(&path, "[output]").unwrap();
+        assert!(watcher.read_events().unwrap());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn parse_colors() {
+        let config = Config::from_toml(
+            r##"
+            [colors]
+            title = "#102030"
+            border = "#10203040"
+            "##,
+        );
+
+        assert_eq!(config.colors.title, Color([16, 32, 48, 255]));
+        assert_eq!(config.colors.border, Color([16, 32, 48, 64]));
+        assert_eq!(config.colors.background, ColorConfig::default().background);
+    }
+
+    #[test]
+    fn invalid_section_fallback() {
+        let config = Config::from_toml(
+            r#"
+            [launcher]
+            command = "launcher"
+
+            [overview]
+            foreground_percentage = 0.5
+            background_percentage = "half"
+            close_distance = 2
+            invalid = 3
+
+            [gestures.edges]
+            left = "invalid"
+            right = "back"
+
+            [[devices]]
+            name = "Touchscreen"
+            rotation = "left"
+
+            [output]
+            scale = 0
+            "#,
+        );
+
+        let default_overview = OverviewConfig::default();
+        assert_eq!(config.launcher.command, "launcher");
+        assert_eq!(config.overview.foreground_percentage, 0.5);
+        assert_eq!(config.overview.background_percentage, default_overview.background_percentage);
+        assert_eq!(config.overview.close_distance, default_overview.close_distance);
+        assert_eq!(config.gestures.edges.left, EdgeBindings::default().left);
+        assert_eq!(config.gestures.edges.right, GestureAction::Back);
+        assert_eq!(config.devices.len(), 1);
+        assert_eq!(config.devices[0].name.as_deref(), Some("Touchscreen"));
+        assert_eq!(config.devices[0].rotation, 0);
+        assert_eq!(config.output, OutputConfig::default());
+    }
+
+    #[test]
+    fn invalid_percentages() {
+        let config = Config::from_toml(
+            r#"
+            [gestures]
+            edge_inset_percentage = 0.5
+
+            [overview]
+            foreground_percentage = 0
+            drag_and_drop_percentage = 0.75
+            "#,
+        );
+
+        let default_gestures = GestureConfig::default();
+        assert_eq!(config.gestures.edge_inset_percentage, default_gestures.edge_inset_percentage);
+        assert_eq!(config.overview, OverviewConfig::default());
+    }
+
+    #[test]
+    fn parse_touchpad() {
+        let config = Config::from_toml(
+            r#"
+            [touchpad]
+            fingers = 4
+            swipe_up = "home"
+            pinch_in = "none"
+            "#,
+        );
+
+        assert_eq!(config.touchpad.fingers, 4);
+        assert_eq!(config.touchpad.swipe_up, GestureAction::Home);
+        assert_eq!(config.touchpad.swipe_down, GestureAction::Home);
+        assert_eq!(config.touchpad.pinch_in, GestureAction::None);
+
+        let config = Config::from_toml(
+            r#"
+            [touchpad]
+            swipe_up = "invalid"
+            "#,
+        );
+        assert_eq!(config.touchpad, TouchpadConfig::default());
+    }
+
+    #[test]
+    fn parse_gesture_bindings() {
+        let config = Config::from_toml(
+            r#"
+            [gestures]
+            swipe_down = { fingers = 4, action = "home" }
+            pinch_in = { action = "overview" }
+            swipe_up = { fingers = 1, action = "overview" }
+            "#,
+        );
+
+        let gestures = config.gestures;
+        assert_eq!(gestures.swipe_down, GestureBinding::new(4, GestureAction::Home));
+        assert_eq!(gestures.pinch_in, GestureBinding::new(3, GestureAction::Overview));
+        assert_eq!(gestures.swipe_up.action, GestureAction::None);
+        assert_eq!(gestures.swipe_left, GestureConfig::default().swipe_left);
+    }
+
+    #[test]
+    fn parse_edge_bindings() {
+        let config = Config::from_toml(
+            r#"
+            [gestures]
+            home_accuracy = 40
+            back_key = "back"
+
+            [gestures.edges]
+            right = "back"
+            top_right = "toggle_secondary"
+            bottom = "none"
+            "#,
+        );
+
+        let gestures = config.gestures;
+        assert_eq!(gestures.edge_size, 40.);
+        assert_eq!(gestures.back_key, BackKey::Back);
+        assert_eq!(gestures.edges.right, GestureAction::Back);
+        assert_eq!(gestures.edges.top_right, GestureAction::ToggleSecondary);
+        assert_eq!(gestures.edges.bottom, GestureAction::None);
+        assert_eq!(gestures.edges.bottom_right, GestureAction::Overview);
+    }
+
+    #[test]
+    fn invalid_friction() {
+        let config = Config::from_toml(
+            r#"
+            [gestures]
+            friction = 1.5
+            min_fling_velocity = 250
+            "#,
+        );
+
+        assert_eq!(config.gestures.friction, GestureConfig::default().friction);
+        assert_eq!(config.gestures.min_fling_velocity, 250.);
+    }
+
+    #[test]
+    fn parse_devices() {
+        let config = Config::from_toml(
+            r#"
+            [[devices]]
+            name = "Goodix Capacitive TouchScreen"
+            calibration_matrix = [0, 1, 0, 1, 0, 0]
+            internal_output = "DSI-1"
+            rotation = 45
+
+            [[devices]]
+            udev = { ID_INPUT_TOUCHPAD = "1" }
+            tap_to_click = true
+            accel_profile = "flat"
+            "#,
+        );
+
+        assert_eq!(config.devices.len(), 2);
+
+        let touchscreen = &config.devices[0];
+        assert_eq!(touchscreen.calibration_matrix, Some([0., 1., 0., 1., 0., 0.]));
+        assert_eq!(touchscreen.internal_output.as_deref(), Some("DSI-1"));
+        assert_eq!(touchscreen.rotation, 0);
+        assert!(touchscreen.matches("Goodix Capacitive TouchScreen", |_| None));
+        assert!(!touchscreen.matches("Keyboard", |_| None));
+
+        let touchpad = &config.devices[1];
+        assert_eq!(touchpad.tap_to_click, Some(true));
+        assert_eq!(touchpad.accel_profile, Some(AccelProfile::Flat));
+        assert!(
+            touchpad.matches("Touchpad", |key| (key == "ID_INPUT_TOUCHPAD").then(|| "1".into()))
+        );
+        assert!(!touchpad.matches("Touchpad", |_| None));
+    }
+
+    #[test]
+    fn parse_switches() {
+        let config = Config::from_toml(
+            r#"
+            [switches]
+            lock_command = "swaylock"
+
+            [switches.tablet_mode]
+            off = ["disable_osk", "landscape"]
+
+            [switches.keypad_slide]
+            on = ["disable_osk"]
+            "#,
+        );
+
+        let switches = config.switches;
+        assert_eq!(switches.lock_command.as_deref(), Some("swaylock"));
+        assert_eq!(switches.lid, SwitchConfig::default().lid);
+        assert!(switches.tablet_mode.on.is_empty());
+        assert_eq!(switches.tablet_mode.off, [SwitchAction::DisableOsk, SwitchAction::Landscape]);
+        assert_eq!(switches.keypad_slide.on, [SwitchAction::DisableOsk]);
+        assert!(switches.keypad_slide.off.is_empty());
+    }
+
+    #[test]
+    fn parse_modes() {
+        let config = Config::from_toml(
+            r#"
+            [modes]
+            DSI-1 = "720x1440"
+            HDMIA-1 = "1920x1080@60"
+            "#,
+        );
+
+        let dsi = OutputMode { width: 720, height: 1440, refresh: None };
+        let hdmi = OutputMode { width: 1920, height: 1080, refresh: Some(60) };
+        assert_eq!(config.modes.get("DSI-1"), Some(&dsi));
+        assert_eq!(config.modes.get("HDMIA-1"), Some(&hdmi));
+
+        let config = Config::from_toml(
+            r#"
+            [modes]
+            HDMIA-1 = "1920x"
+            "#,
+        );
+        assert!(config.modes.is_empty());
+    }
+}
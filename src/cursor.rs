@@ -0,0 +1,249 @@
+//! Pointer cursor.
+//!
+//! Clients can set their own cursor surface while they have pointer focus.
+//! Otherwise the `default` cursor of the XCursor theme selected through the
+//! `XCURSOR_THEME` and `XCURSOR_SIZE` environment variables is used.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::{env, fs};
+
+use log::warn;
+use smithay::backend::renderer::gles2::{Gles2Frame, Gles2Renderer};
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::utils::{Logical, Physical, Point, Rectangle};
+use smithay::wayland::compositor;
+use smithay::wayland::seat::CursorImageStatus;
+use xcursor::parser::{self, Image};
+use xcursor::CursorTheme;
+
+use crate::drawing::Texture;
+use crate::output::Output;
+use crate::window::{CursorSurface, Window};
+
+/// XCursor theme used without `XCURSOR_THEME`.
+const DEFAULT_THEME: &str = "default";
+
+/// Cursor size used without `XCURSOR_SIZE`, with a DPR of 1.
+const DEFAULT_SIZE: u32 = 24;
+
+/// Theme cursor names, in order of preference.
+const DEFAULT_ICONS: [&str; 2] = ["default", "left_ptr"];
+
+/// Pointer cursor state.
+pub struct Cursor {
+    /// Pointer location.
+    pub position: Point<f64, Logical>,
+
+    /// Cursor image last requested by a client.
+    pending_image: Rc<RefCell<Option<CursorImageStatus>>>,
+    image: CursorImage,
+
+    /// Theme cursor images, in all available sizes.
+    theme_images: Vec<Image>,
+    theme_texture: Option<(usize, Texture)>,
+    theme_size: u32,
+
+    visible: bool,
+    damaged: bool,
+}
+
+impl Cursor {
+    pub fn new() -> Self {
+        let theme_size = env::var("XCURSOR_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_SIZE);
+
+        Self {
+            theme_images: load_theme_images(),
+            theme_size,
+            position: Default::default(),
+            pending_image: Default::default(),
+            theme_texture: Default::default(),
+            visible: Default::default(),
+            damaged: Default::default(),
+            image: CursorImage::Default,
+        }
+    }
+
+    /// Callback for cursor image changes requested by clients.
+    pub fn image_callback(&self) -> impl FnMut(CursorImageStatus) {
+        let pending_image = self.pending_image.clone();
+        move |status| *pending_image.borrow_mut() = Some(status)
+    }
+
+    /// Show the cursor after pointer input.
+    pub fn show(&mut self) {
+        self.damaged |= !self.visible;
+        self.visible = true;
+    }
+
+    /// Hide the cursor while touch input is used.
+    pub fn hide(&mut self) {
+        self.damaged |= self.visible;
+        self.visible = false;
+    }
+
+    /// Move the cursor.
+    pub fn set_position(&mut self, position: Point<f64, Logical>) {
+        self.damaged |= self.visible && position != self.position;
+        self.position = position;
+    }
+
+    /// Check if the cursor needs to be redrawn.
+    pub fn damaged(&self) -> bool {
+        self.damaged || (self.visible && self.pending_image.borrow().is_some())
+    }
+
+    /// Handle a surface commit for the client cursor surface.
+    ///
+    /// Returns `false` if the surface is not part of the cursor.
+    pub fn surface_commit(&mut self, surface: &WlSurface, output: &Output) -> bool {
+        self.update_image(output);
+
+        let window = match &mut self.image {
+            CursorImage::Surface(window) => window,
+            _ => return false,
+        };
+
+        // Get the topmost surface for cursor comparison.
+        let mut root_surface = Cow::Borrowed(surface);
+        while let Some(parent) = compositor::get_parent(&root_surface) {
+            root_surface = Cow::Owned(parent);
+        }
+
+        if !window.uses_surface(&root_surface) {
+            return false;
+        }
+
+        window.surface_commit(surface, output);
+        self.damaged = true;
+
+        true
+    }
+
+    /// Send a frame request to the client cursor surface.
+    pub fn request_frame(&mut self, runtime: u32) {
+        if let CursorImage::Surface(window) = &mut self.image {
+            window.request_frame(runtime);
+        }
+    }
+
+    /// Render the cursor.
+    pub fn draw(
+        &mut self,
+        renderer: &mut Gles2Renderer,
+        frame: &mut Gles2Frame,
+        output: &Output,
+        damage: &[Rectangle<f64, Physical>],
+    ) {
+        self.update_image(output);
+        self.damaged = false;
+
+        if !self.visible {
+            return;
+        }
+
+        match &mut self.image {
+            CursorImage::Surface(window) => {
+                let loc = self.position.to_i32_round() - window.hotspot();
+                let bounds = Rectangle::from_loc_and_size(loc, (i32::MAX, i32::MAX));
+                window.draw(renderer, frame, output, 1., bounds, damage);
+            },
+            CursorImage::Default => self.draw_theme_cursor(renderer, frame, output, damage),
+            CursorImage::Hidden => (),
+        }
+    }
+
+    /// Render the default cursor of the XCursor theme.
+    fn draw_theme_cursor(
+        &mut self,
+        renderer: &mut Gles2Renderer,
+        frame: &mut Gles2Frame,
+        output: &Output,
+        damage: &[Rectangle<f64, Physical>],
+    ) {
+        // Pick the image closest to the cursor size in physical pixels.
+        let scale = output.scale();
+        let size = (self.theme_size as f64 * scale).round() as i64;
+        let index = self
+            .theme_images
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, image)| (image.size as i64 - size).abs())
+            .map(|(index, _)| index);
+        let index = match index {
+            Some(index) => index,
+            None => return,
+        };
+
+        // Upload the image when the required size changed.
+        let image = &self.theme_images[index];
+        let texture = match &mut self.theme_texture {
+            Some((texture_index, texture)) if *texture_index == index => texture,
+            theme_texture => {
+                let (width, height) = (image.width as i32, image.height as i32);
+                let texture = Texture::from_buffer(renderer, &image.pixels_rgba, width, height);
+                &mut theme_texture.insert((index, texture)).1
+            },
+        };
+
+        let hotspot = Point::<_, Physical>::from((image.xhot as f64, image.yhot as f64));
+        let hotspot = hotspot.to_logical(scale);
+        let loc = (self.position - hotspot).to_i32_round();
+        let bounds = Rectangle::from_loc_and_size(loc, (i32::MAX, i32::MAX));
+        for damage in damage {
+            texture.draw_at(frame, output, bounds, 1. / scale, *damage);
+        }
+    }
+
+    /// Apply the latest cursor image requested by a client.
+    fn update_image(&mut self, output: &Output) {
+        let status = match self.pending_image.borrow_mut().take() {
+            Some(status) => status,
+            None => return,
+        };
+        self.damaged = true;
+
+        self.image = match status {
+            CursorImageStatus::Image(surface) => match &self.image {
+                CursorImage::Surface(window) if window.uses_surface(&surface) => return,
+                _ => {
+                    // Import buffers committed before the surface became the cursor.
+                    let mut window = Window::new(CursorSurface::from(surface.clone()));
+                    window.surface_commit(&surface, output);
+                    CursorImage::Surface(window)
+                },
+            },
+            CursorImageStatus::Default => CursorImage::Default,
+            CursorImageStatus::Hidden => CursorImage::Hidden,
+        };
+    }
+}
+
+/// Source of the cursor's image.
+enum CursorImage {
+    Surface(Window<CursorSurface>),
+    Default,
+    Hidden,
+}
+
+/// Load the default cursor images from the XCursor theme.
+fn load_theme_images() -> Vec<Image> {
+    let theme_name = env::var("XCURSOR_THEME").unwrap_or_else(|_| DEFAULT_THEME.into());
+    let theme = CursorTheme::load(&theme_name);
+
+    let path = DEFAULT_ICONS.iter().find_map(|icon| theme.load_icon(icon));
+    let images = path
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|data| parser::parse_xcursor(&data))
+        .unwrap_or_default();
+
+    if images.is_empty() {
+        warn!("Unable to load default cursor from XCursor theme {theme_name:?}");
+    }
+
+    images
+}
@@ -0,0 +1,21 @@
+//! Background process management.
+
+use std::ffi::OsStr;
+use std::io;
+use std::process::{Command, Stdio};
+
+/// Start a new process in the background.
+pub fn spawn<I, S>(program: impl AsRef<OsStr>, args: I) -> io::Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    Ok(())
+}
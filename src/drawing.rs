@@ -4,33 +4,15 @@ use std::ops::Deref;
 use std::rc::Rc;
 use std::vec::Drain;
 
-use smithay::backend::renderer::gles2::{ffi, Gles2Frame, Gles2Renderer, Gles2Texture};
+use smithay::backend::renderer::gles2::{ffi, Gles2Error, Gles2Frame, Gles2Renderer, Gles2Texture};
 use smithay::backend::renderer::{self, Frame};
 use smithay::reexports::wayland_server::protocol::wl_buffer::WlBuffer;
 use smithay::utils::{Buffer as BufferSpace, Logical, Physical, Point, Rectangle, Size, Transform};
 use smithay::wayland::compositor::{BufferAssignment, Damage as SurfaceDamage, SurfaceAttributes};
 
+use crate::config::{ColorConfig, Config, OverviewConfig};
 use crate::geometry::Vector;
 use crate::output::Output;
-use crate::overview::FG_OVERVIEW_PERCENTAGE;
-
-/// Maximum buffer age before damage information is discarded.
-pub const MAX_DAMAGE_AGE: usize = 2;
-
-/// Color of the hovered overview tiling location highlight.
-const ACTIVE_DROP_TARGET_RGBA: [u8; 4] = [128, 128, 128, 128];
-
-/// Color of the overview tiling location highlight.
-const DROP_TARGET_RGBA: [u8; 4] = [128, 128, 128, 64];
-
-/// Background color behind half-size windows in the overview.
-const BACKGROUND_RGBA: [u8; 4] = [0, 0, 0, 255];
-
-/// Decoration titlebar color in the overview.
-const TITLE_RGBA: [u8; 4] = [64, 64, 64, 255];
-
-/// Decoration border color in the overview.
-const BORDER_RGBA: [u8; 4] = [32, 32, 32, 255];
 
 /// Height of the window decoration title in the application overview with a DPR
 /// of 1.
@@ -117,6 +99,54 @@ impl Texture {
         Texture::new(Rc::new(texture), (width, height))
     }
 
+    /// Copy the currently bound framebuffer into a texture.
+    ///
+    /// The existing `texture` is reused if its size and transform still match,
+    /// otherwise a new texture is allocated.
+    ///
+    /// The framebuffer's content is expected to be rendered with the output
+    /// `transform`, which is reverted when drawing the texture.
+    pub fn copy_framebuffer(
+        renderer: &mut Gles2Renderer,
+        texture: &mut Option<Self>,
+        size: Size<i32, Physical>,
+        transform: Transform,
+    ) -> Result<(), Gles2Error> {
+        let (width, height) = transform.transform_size(size).into();
+        let logical_size = Size::from((width, height));
+
+        // Update the existing texture in place.
+        if let Some(texture) = texture
+            .as_mut()
+            .filter(|texture| texture.size == logical_size && texture.transform == transform)
+        {
+            let tex = texture.texture.tex_id();
+            return renderer.with_context(|_, gl| unsafe {
+                gl.BindTexture(ffi::TEXTURE_2D, tex);
+                gl.CopyTexSubImage2D(ffi::TEXTURE_2D, 0, 0, 0, 0, 0, size.w, size.h);
+                gl.BindTexture(ffi::TEXTURE_2D, 0);
+            });
+        }
+
+        let new_texture = renderer.with_context(|renderer, gl| unsafe {
+            let mut tex = 0;
+            gl.GenTextures(1, &mut tex);
+            gl.BindTexture(ffi::TEXTURE_2D, tex);
+            gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_S, ffi::CLAMP_TO_EDGE as i32);
+            gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_T, ffi::CLAMP_TO_EDGE as i32);
+            gl.CopyTexImage2D(ffi::TEXTURE_2D, 0, ffi::RGBA, 0, 0, size.w, size.h, 0);
+            gl.BindTexture(ffi::TEXTURE_2D, 0);
+
+            Gles2Texture::from_raw(renderer, tex, (size.w, size.h).into())
+        })?;
+
+        let mut new_texture = Texture::new(Rc::new(new_texture), logical_size);
+        new_texture.transform = transform;
+        *texture = Some(new_texture);
+
+        Ok(())
+    }
+
     /// Render the texture at the specified location.
     ///
     /// Using the `window_bounds` and `window_scale` parameters, it is possible
@@ -183,31 +213,66 @@ pub struct Graphics {
     active_drop_target: Option<Texture>,
     drop_target: Option<Texture>,
     decoration: Option<Texture>,
+    back_indicator: Option<Texture>,
     touch_debug: Option<Texture>,
+
+    /// Colors used for the cached textures.
+    colors: ColorConfig,
 }
 
 impl Graphics {
     /// Get the window decoration texture corresponding to the active output
     /// size.
-    pub fn decoration(&mut self, renderer: &mut Gles2Renderer, output: &Output) -> &mut Texture {
-        let expected_size = Self::decoration_size(output);
+    pub fn decoration(
+        &mut self,
+        renderer: &mut Gles2Renderer,
+        output: &Output,
+        config: &Config,
+    ) -> &mut Texture {
+        self.update_colors(&config.colors);
+
+        let expected_size = Self::decoration_size(output, &config.overview);
         if self.decoration.as_ref().map(|decoration| decoration.size) != Some(expected_size) {
             self.decoration = None;
         }
 
-        self.decoration.get_or_insert_with(|| Self::create_decoration(renderer, output))
+        self.decoration.get_or_insert_with(|| Self::create_decoration(renderer, output, config))
     }
 
     /// Get the texture for the hovered overview drop target area.
-    pub fn active_drop_target(&mut self, renderer: &mut Gles2Renderer) -> &mut Texture {
-        self.active_drop_target
-            .get_or_insert_with(|| Texture::from_buffer(renderer, &ACTIVE_DROP_TARGET_RGBA, 1, 1))
+    pub fn active_drop_target(
+        &mut self,
+        renderer: &mut Gles2Renderer,
+        colors: &ColorConfig,
+    ) -> &mut Texture {
+        self.update_colors(colors);
+
+        let rgba = colors.active_drop_target.rgba();
+        self.active_drop_target.get_or_insert_with(|| Texture::from_buffer(renderer, &rgba, 1, 1))
     }
 
     /// Get the texture for the unfocused overview drop target area.
-    pub fn drop_target(&mut self, renderer: &mut Gles2Renderer) -> &mut Texture {
-        self.drop_target
-            .get_or_insert_with(|| Texture::from_buffer(renderer, &DROP_TARGET_RGBA, 1, 1))
+    pub fn drop_target(
+        &mut self,
+        renderer: &mut Gles2Renderer,
+        colors: &ColorConfig,
+    ) -> &mut Texture {
+        self.update_colors(colors);
+
+        let rgba = colors.drop_target.rgba();
+        self.drop_target.get_or_insert_with(|| Texture::from_buffer(renderer, &rgba, 1, 1))
+    }
+
+    /// Get the texture for the back gesture indicator.
+    pub fn back_indicator(
+        &mut self,
+        renderer: &mut Gles2Renderer,
+        colors: &ColorConfig,
+    ) -> &mut Texture {
+        self.update_colors(colors);
+
+        let rgba = colors.back_indicator.rgba();
+        self.back_indicator.get_or_insert_with(|| Texture::from_buffer(renderer, &rgba, 1, 1))
     }
 
     pub fn touch_debug(&mut self, renderer: &mut Gles2Renderer) -> &mut Texture {
@@ -231,9 +296,27 @@ impl Graphics {
         (OVERVIEW_BORDER_WIDTH as f64 / output.scale()).round() as i32
     }
 
+    /// Discard all cached textures affected by a change of the colors.
+    fn update_colors(&mut self, colors: &ColorConfig) {
+        if *colors == self.colors {
+            return;
+        }
+
+        self.active_drop_target = None;
+        self.drop_target = None;
+        self.decoration = None;
+        self.back_indicator = None;
+        self.colors = *colors;
+    }
+
     /// Create overview window decoration.
-    fn create_decoration(renderer: &mut Gles2Renderer, output: &Output) -> Texture {
-        let size = Self::decoration_size(output);
+    fn create_decoration(
+        renderer: &mut Gles2Renderer,
+        output: &Output,
+        config: &Config,
+    ) -> Texture {
+        let colors = &config.colors;
+        let size = Self::decoration_size(output, &config.overview);
         let title_height = Self::title_height(output) as usize;
         let border_width = Self::border_width(output) as usize;
 
@@ -255,36 +338,37 @@ impl Graphics {
         // Background.
         let right_border = width - border_width;
         let bottom_border = height - border_width;
-        fill(border_width, right_border, title_height, bottom_border, BACKGROUND_RGBA);
+        fill(border_width, right_border, title_height, bottom_border, colors.background.rgba());
 
         // Titlebar.
-        fill(border_width, width, border_width, title_height - border_width, TITLE_RGBA);
+        fill(border_width, width, border_width, title_height - border_width, colors.title.rgba());
 
         // Titlebar top border.
-        fill(border_width, right_border, 0, border_width, BORDER_RGBA);
+        let border = colors.border.rgba();
+        fill(border_width, right_border, 0, border_width, border);
 
         // Titlebar bottom border.
         let title_border = title_height - border_width;
-        fill(border_width, right_border, title_border, title_height, BORDER_RGBA);
+        fill(border_width, right_border, title_border, title_height, border);
 
         // Left border.
-        fill(0, border_width, 0, height, BORDER_RGBA);
+        fill(0, border_width, 0, height, border);
 
         // Right border.
-        fill(right_border, width, 0, height, BORDER_RGBA);
+        fill(right_border, width, 0, height, border);
 
         // Bottom border.
-        fill(border_width, right_border, bottom_border, height, BORDER_RGBA);
+        fill(border_width, right_border, bottom_border, height, border);
 
         Texture::from_buffer(renderer, &buffer, size.w, size.h)
     }
 
     /// Total window decoration size.
-    fn decoration_size(output: &Output) -> Size<i32, Logical> {
+    fn decoration_size(output: &Output, config: &OverviewConfig) -> Size<i32, Logical> {
         let title_height = Self::title_height(output);
         let border_width = Self::border_width(output);
 
-        let window_size = output.available().size.scale(FG_OVERVIEW_PERCENTAGE);
+        let window_size = output.available().size.scale(config.foreground_percentage);
         let width = window_size.w + border_width * 2;
         let height = window_size.h + title_height + border_width;
 
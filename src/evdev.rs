@@ -0,0 +1,150 @@
+//! Evdev switches which are not reported by libinput.
+
+use std::io::{self, ErrorKind};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::PathBuf;
+use std::{mem, ptr};
+
+/// Evdev event type of switches.
+const EV_SW: u16 = 0x05;
+
+/// Switch code of slide-out keyboards.
+const SW_KEYPAD_SLIDE: u16 = 0x0a;
+
+/// Size of a bitmask covering all switch codes.
+const SW_BITS_LEN: usize = 3;
+
+/// Ioctl number for reading the supported switches.
+const EVIOCGBIT_SW: u64 = 0x20 + EV_SW as u64;
+
+/// Ioctl number for reading the current switch states.
+const EVIOCGSW: u64 = 0x1b;
+
+/// Slide-out keyboard switch of an evdev device.
+///
+/// The file descriptor is owned by the session which opened it.
+pub struct KeypadSlide {
+    fd: RawFd,
+}
+
+impl KeypadSlide {
+    /// Use an evdev device as keypad slide switch.
+    ///
+    /// Returns `None` if the device has no keypad slide switch.
+    pub fn new(fd: RawFd) -> Option<Self> {
+        let keypad_slide = Self { fd };
+        let switches = keypad_slide.read_bits(EVIOCGBIT_SW).ok()?;
+        has_bit(&switches, SW_KEYPAD_SLIDE).then(|| keypad_slide)
+    }
+
+    /// Check if the keyboard is currently exposed.
+    pub fn state(&self) -> io::Result<bool> {
+        let states = self.read_bits(EVIOCGSW)?;
+        Ok(has_bit(&states, SW_KEYPAD_SLIDE))
+    }
+
+    /// Consume all pending events.
+    ///
+    /// Returns the last reported switch state, if it was toggled.
+    pub fn read_events(&mut self) -> io::Result<Option<bool>> {
+        let mut buffer = [0u8; 64 * mem::size_of::<libc::input_event>()];
+        let mut state = None;
+
+        loop {
+            let read = unsafe { libc::read(self.fd, buffer.as_mut_ptr().cast(), buffer.len()) };
+            if read < 0 {
+                let err = io::Error::last_os_error();
+                return match err.kind() {
+                    ErrorKind::WouldBlock => Ok(state),
+                    _ => Err(err),
+                };
+            } else if read == 0 {
+                return Ok(state);
+            }
+
+            let events = buffer[..read as usize].chunks_exact(mem::size_of::<libc::input_event>());
+            for event in events {
+                let event: libc::input_event =
+                    unsafe { ptr::read_unaligned(event.as_ptr().cast()) };
+                if event.type_ == EV_SW && event.code == SW_KEYPAD_SLIDE {
+                    state = Some(event.value != 0);
+                }
+            }
+        }
+    }
+
+    /// Read a switch bitmask using an evdev ioctl.
+    fn read_bits(&self, nr: u64) -> io::Result<[u8; SW_BITS_LEN]> {
+        let mut bits = [0u8; SW_BITS_LEN];
+
+        // Equivalent to the kernel's `_IOC(_IOC_READ, 'E', nr, len)`.
+        let request = (2 << 30) | ((SW_BITS_LEN as u64) << 16) | ((b'E' as u64) << 8) | nr;
+        if unsafe { libc::ioctl(self.fd, request as _, bits.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(bits)
+    }
+}
+
+impl AsRawFd for KeypadSlide {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+/// Get the device nodes of all evdev switch devices.
+pub fn switch_devices() -> io::Result<Vec<PathBuf>> {
+    let mut enumerator = udev::Enumerator::new()?;
+    enumerator.match_subsystem("input")?;
+    enumerator.match_property("ID_INPUT_SWITCH", "1")?;
+
+    let devices = enumerator.scan_devices()?;
+    Ok(devices.filter_map(|device| device.devnode().map(|path| path.to_path_buf())).collect())
+}
+
+/// Check if a bit is set in an evdev bitmask.
+fn has_bit(bits: &[u8], bit: u16) -> bool {
+    bits[bit as usize / 8] & (1 << (bit % 8)) != 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Write a single event to a file descriptor.
+    fn write_event(fd: RawFd, type_: u16, code: u16, value: i32) {
+        let event =
+            libc::input_event { time: libc::timeval { tv_sec: 0, tv_usec: 0 }, type_, code, value };
+        let size = mem::size_of::<libc::input_event>();
+        let written = unsafe { libc::write(fd, ptr::addr_of!(event).cast(), size) };
+        assert_eq!(written, size as isize);
+    }
+
+    #[test]
+    fn keypad_slide_events() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK) }, 0);
+        let mut keypad_slide = KeypadSlide { fd: fds[0] };
+
+        assert_eq!(keypad_slide.read_events().unwrap(), None);
+
+        // Only the last state of the keypad slide switch is reported.
+        write_event(fds[1], EV_SW, SW_KEYPAD_SLIDE, 1);
+        write_event(fds[1], EV_SW, 0x00, 0);
+        write_event(fds[1], EV_SW, SW_KEYPAD_SLIDE, 0);
+        write_event(fds[1], EV_SW, SW_KEYPAD_SLIDE, 1);
+        assert_eq!(keypad_slide.read_events().unwrap(), Some(true));
+
+        write_event(fds[1], 0x00, 0x00, 0);
+        assert_eq!(keypad_slide.read_events().unwrap(), None);
+
+        // Pipes have no switches.
+        assert!(KeypadSlide::new(fds[0]).is_none());
+
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+}
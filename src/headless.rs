@@ -0,0 +1,187 @@
+//! Headless backend.
+//!
+//! This backend renders into an offscreen buffer using a surfaceless EGL
+//! context, which allows running the compositor without any display or DRM
+//! device.
+
+use std::error::Error;
+use std::ffi::c_void;
+use std::ptr;
+use std::time::Duration;
+
+use log::error;
+use smithay::backend::egl::context::EGLContext;
+use smithay::backend::egl::display::EGLDisplay;
+use smithay::backend::egl::ffi;
+use smithay::backend::egl::native::{EGLNativeDisplay, EGLPlatform};
+use smithay::backend::renderer::gles2::{Gles2Frame, Gles2Renderbuffer, Gles2Renderer};
+use smithay::backend::renderer::{Bind, ImportEgl, Offscreen, Renderer};
+use smithay::reexports::calloop::timer::Timer;
+use smithay::reexports::calloop::EventLoop;
+use smithay::reexports::wayland_server::protocol::wl_output::Subpixel;
+use smithay::utils::{Buffer, Size};
+use smithay::wayland::output::{Mode, PhysicalProperties};
+
+use crate::catacomb::{Backend, Catacomb, Render, Startup};
+use crate::config::Config;
+use crate::logging;
+use crate::output::Output;
+
+/// EGL platform for rendering without any native display.
+const PLATFORM_SURFACELESS_MESA: ffi::egl::types::EGLenum = 0x31DD;
+
+pub fn run(config: Config, startup: Startup) -> Result<(), Box<dyn Error>> {
+    let mut event_loop = EventLoop::try_new()?;
+    let mut catacomb = Catacomb::new(&mut event_loop, config, startup, Headless::default())?;
+
+    // Create the virtual output.
+    let headless = catacomb.config.headless;
+    let mode = Mode {
+        size: (headless.width, headless.height).into(),
+        refresh: headless.refresh as i32 * 1000,
+    };
+    let properties = PhysicalProperties {
+        subpixel: Subpixel::Unknown,
+        model: "Headless".into(),
+        make: "Catacomb".into(),
+        size: (0, 0).into(),
+    };
+    let mut display = catacomb.display.borrow_mut();
+    let scale = catacomb.config.output.scale;
+    let output = Output::new(&mut display, "HEADLESS-1", mode, properties, scale);
+    drop(display);
+    catacomb.output = output;
+
+    // Create the offscreen renderer.
+    let output_device = HeadlessDevice::new(&catacomb)?;
+    catacomb.backend.output_device = Some(output_device);
+
+    // Continuously render at the virtual output's refresh rate.
+    let frame_interval = Duration::from_millis(catacomb.output.frame_interval());
+    let timer = Timer::new()?;
+    timer.handle().add_timeout(frame_interval, ());
+    event_loop
+        .handle()
+        .insert_source(timer, move |_, handle, catacomb| {
+            catacomb.render();
+            handle.add_timeout(frame_interval, ());
+        })
+        .expect("setting up render timer callback");
+
+    // Continously dispatch event loop.
+    let display = catacomb.display.clone();
+    loop {
+        if let Err(error) = event_loop.dispatch(None, &mut catacomb) {
+            error!("Event loop error: {error}");
+            break;
+        }
+        display.borrow_mut().flush_clients(&mut catacomb);
+    }
+
+    Ok(())
+}
+
+/// Headless backend shared state.
+#[derive(Default)]
+pub struct Headless {
+    output_device: Option<HeadlessDevice>,
+}
+
+impl Backend for Headless {
+    fn seat_name(&self) -> String {
+        String::from("seat-0")
+    }
+}
+
+impl Catacomb<Headless> {
+    /// Render the virtual output.
+    fn render(&mut self) {
+        let mut device = self.backend.output_device.take();
+        if let Some(device) = device.as_mut() {
+            self.create_frame(device);
+        }
+        self.backend.output_device = device;
+    }
+}
+
+/// Offscreen rendering target.
+pub struct HeadlessDevice {
+    renderer: Gles2Renderer,
+    buffer: Gles2Renderbuffer,
+    buffer_size: Size<i32, Buffer>,
+
+    /// Age of the offscreen buffer's content.
+    age: u8,
+}
+
+impl HeadlessDevice {
+    pub fn new<B>(catacomb: &Catacomb<B>) -> Result<Self, Box<dyn Error>> {
+        let display = EGLDisplay::new(&Surfaceless, logging::smithay())?;
+        let context = EGLContext::new(&display, logging::smithay())?;
+        let mut renderer = unsafe { Gles2Renderer::new(context, logging::smithay())? };
+
+        // Allow clients to use EGL buffers, if supported by the driver.
+        let _ = renderer.bind_wl_display(&catacomb.display.borrow());
+
+        let buffer_size = physical_buffer_size(catacomb);
+        let buffer = Offscreen::<Gles2Renderbuffer>::create_buffer(&mut renderer, buffer_size)?;
+
+        Ok(Self { renderer, buffer, buffer_size, age: 0 })
+    }
+}
+
+impl Render for &mut HeadlessDevice {
+    fn render<B, F>(
+        &mut self,
+        catacomb: &mut Catacomb<B>,
+        draw_fun: F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(&mut Catacomb<B>, &mut Gles2Renderer, &mut Gles2Frame, u8),
+    {
+        // Recreate the buffer when the output mode changed.
+        let buffer_size = physical_buffer_size(catacomb);
+        if buffer_size != self.buffer_size {
+            self.buffer =
+                Offscreen::<Gles2Renderbuffer>::create_buffer(&mut self.renderer, buffer_size)?;
+            self.buffer_size = buffer_size;
+            self.age = 0;
+        }
+
+        self.renderer.bind(self.buffer.clone())?;
+
+        // Draw the current frame into the buffer.
+        let transform = catacomb.windows.orientation().transform();
+        let output_size = catacomb.output.physical_resolution();
+        let age = self.age;
+        self.renderer.render(output_size, transform, |renderer, frame| {
+            draw_fun(catacomb, renderer, frame, age)
+        })?;
+
+        // The same buffer is reused, so it always contains the previous frame.
+        self.age = 1;
+
+        Ok(())
+    }
+}
+
+/// Output size in buffer coordinates.
+fn physical_buffer_size<B>(catacomb: &Catacomb<B>) -> Size<i32, Buffer> {
+    let size = catacomb.output.physical_resolution();
+    (size.w, size.h).into()
+}
+
+/// Native display for Mesa's surfaceless EGL platform.
+struct Surfaceless;
+
+impl EGLNativeDisplay for Surfaceless {
+    fn supported_platforms(&self) -> Vec<EGLPlatform<'_>> {
+        vec![EGLPlatform::new(
+            PLATFORM_SURFACELESS_MESA,
+            "PLATFORM_SURFACELESS_MESA",
+            ptr::null_mut::<c_void>(),
+            vec![ffi::egl::NONE as ffi::EGLint],
+            &["EGL_MESA_platform_surfaceless"],
+        )]
+    }
+}
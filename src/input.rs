@@ -1,43 +1,73 @@
 //! Input event handling.
 
-use std::process::{Command, Stdio};
+use std::collections::VecDeque;
+use std::mem;
 use std::time::{Duration, Instant};
 
 use calloop::timer::{Timer, TimerHandle};
 use calloop::LoopHandle;
+use catacomb_ipc::IpcEvent;
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
 use smithay::backend::input::{
-    ButtonState, Event, InputBackend, InputEvent, KeyState, KeyboardKeyEvent, MouseButton,
-    PointerButtonEvent, PositionEvent, TouchEvent as _, TouchSlot,
+    Axis, AxisSource, ButtonState, Device, Event, GestureBeginEvent, GestureEndEvent,
+    GesturePinchUpdateEvent, GestureSwipeUpdateEvent, InputBackend, InputEvent, KeyState,
+    KeyboardKeyEvent, MouseButton, PointerAxisEvent, PointerButtonEvent, PointerMotionEvent,
+    PositionEvent, Switch, SwitchState, SwitchToggleEvent, TouchEvent as _, TouchSlot,
 };
 #[cfg(feature = "winit")]
 use smithay::backend::winit::WinitEvent;
+use smithay::reexports::wayland_server::protocol::wl_pointer;
 use smithay::utils::{Logical, Point, Rectangle, Size};
-use smithay::wayland::seat::{keysyms, FilterResult, TouchHandle};
+use smithay::wayland::seat::{keysyms, AxisFrame, FilterResult, TouchHandle};
 use smithay::wayland::SERIAL_COUNTER;
 
 use crate::catacomb::{Backend, Catacomb};
-use crate::config::APP_DRAWER;
+use crate::config::{
+    DeviceConfig, EdgeBindings, GestureAction, GestureConfig, LauncherConfig, SwitchAction,
+    SwitchBinding,
+};
 use crate::orientation::Orientation;
 use crate::output::Output;
+use crate::recording::InputRecord;
 use crate::window::OffsetSurface;
+use crate::{clock, daemon};
 
 /// Time before a tap is considered a hold.
 pub const HOLD_DURATION: Duration = Duration::from_secs(1);
 
-/// Accepted overview gesture deviation in pixels at scale 1.
-const OVERVIEW_GESTURE_ACCURACY: f64 = 60.;
+/// Fraction of the output an edge swipe has to travel inward.
+const EDGE_SWIPE_PERCENTAGE: f64 = 0.25;
 
-/// Accepted home gesture deviation in pixels at scale 1.
-const HOME_GESTURE_ACCURACY: f64 = 30.;
+/// Maximum width of the back gesture indicator.
+const BACK_INDICATOR_WIDTH: f64 = 20.;
 
-/// Home gesture distance from the output edges.
-const HOME_WIDTH_PERCENTAGE: f64 = 0.25;
+/// Length of the back gesture indicator along the output edge.
+const BACK_INDICATOR_LENGTH: f64 = 120.;
 
 /// Maximum distance before touch input is considered a drag.
 const MAX_TAP_DISTANCE: f64 = 20.;
 
-/// Friction for velocity computation.
-const FRICTION: f64 = 0.1;
+/// Duration of touch history used for velocity estimation in milliseconds.
+const VELOCITY_WINDOW: u32 = 100;
+
+/// Minimum distance before a multi-finger swipe is recognized.
+const MIN_MULTI_TOUCH_SWIPE_DISTANCE: f64 = 50.;
+
+/// Minimum distance before a two-finger swipe is recognized.
+///
+/// Two-finger swipes are also used for panning inside windows, so clients keep
+/// receiving the touches for longer before the gesture takes over.
+const MIN_TWO_FINGER_SWIPE_DISTANCE: f64 = 150.;
+
+/// Minimum scale change before a multi-finger pinch is recognized.
+const MIN_MULTI_TOUCH_PINCH_SCALE: f64 = 0.25;
+
+/// Minimum distance before a touchpad swipe triggers its action.
+const MIN_TOUCHPAD_SWIPE_DISTANCE: f64 = 100.;
+
+/// Minimum scale change before a touchpad pinch triggers its action.
+const MIN_TOUCHPAD_PINCH_SCALE: f64 = 0.25;
 
 /// Touch slot for pointer emulation.
 ///
@@ -51,11 +81,21 @@ pub struct TouchState {
     pub position: Point<f64, Logical>,
     slot: Option<TouchSlot>,
     velocity: Point<f64, Logical>,
+    /// Recent positions of the active touch point, with their timestamps.
+    history: VecDeque<(u32, Point<f64, Logical>)>,
     events: Vec<TouchEvent>,
     timer: TimerHandle<()>,
     touch: TouchHandle,
     start: TouchStart,
     is_drag: bool,
+    touchpad_gesture: Option<TouchpadGesture>,
+
+    /// Touch sequence withheld from clients during gesture recognition.
+    held_touch: Option<HeldTouch>,
+
+    /// Positions of all active touch points.
+    points: Vec<(TouchSlot, Point<f64, Logical>)>,
+    multi_touch: Option<MultiTouch>,
 }
 
 impl TouchState {
@@ -71,10 +111,15 @@ impl TouchState {
             touch,
             position: Default::default(),
             velocity: Default::default(),
+            history: Default::default(),
             is_drag: Default::default(),
             events: Default::default(),
             start: Default::default(),
             slot: Default::default(),
+            touchpad_gesture: Default::default(),
+            held_touch: Default::default(),
+            multi_touch: Default::default(),
+            points: Default::default(),
         }
     }
 
@@ -85,12 +130,176 @@ impl TouchState {
     }
 
     /// Start a new touch session.
-    fn start(&mut self, output: &Output, position: Point<f64, Logical>) {
-        self.start = TouchStart::new(output, position);
+    fn start(
+        &mut self,
+        output: &Output,
+        config: &GestureConfig,
+        time: u32,
+        position: Point<f64, Logical>,
+    ) {
+        self.start = TouchStart::new(output, config, position);
         self.velocity = Default::default();
         self.timer.cancel_all_timeouts();
         self.position = position;
+        self.held_touch = None;
+        self.is_drag = false;
+
+        self.history.clear();
+        self.history.push_back((time, position));
+    }
+
+    /// Add a position of the active touch point to its history.
+    fn push_history(&mut self, time: u32, position: Point<f64, Logical>) {
+        self.history.push_back((time, position));
+        self.prune_history(time);
+    }
+
+    /// Remove history entries outside of the velocity window.
+    fn prune_history(&mut self, time: u32) {
+        while let Some((entry_time, _)) = self.history.front() {
+            if time.wrapping_sub(*entry_time) <= VELOCITY_WINDOW {
+                break;
+            }
+            self.history.pop_front();
+        }
+    }
+
+    /// Estimate the active touch point's velocity from its history.
+    ///
+    /// The velocity is in logical pixels per 16ms, which is used for all
+    /// animations independent of the refresh rate.
+    fn estimate_velocity(&self) -> Point<f64, Logical> {
+        let ((start_time, start), (end_time, end)) =
+            match (self.history.front(), self.history.back()) {
+                (Some(start), Some(end)) => (start, end),
+                _ => return Point::default(),
+            };
+
+        let duration = end_time.wrapping_sub(*start_time);
+        if duration == 0 {
+            return Point::default();
+        }
+
+        (*end - *start) * (16. / duration as f64)
+    }
+
+    /// Get the velocity for a fling after the active touch point was released.
+    fn fling_velocity(&mut self, config: &GestureConfig, time: u32) -> Point<f64, Logical> {
+        // Ignore positions from before the touch point came to rest.
+        self.prune_history(time);
+
+        let velocity = self.estimate_velocity();
+        let speed = f64::sqrt(velocity.x.powi(2) + velocity.y.powi(2)) * 1000. / 16.;
+        if speed < config.min_fling_velocity {
+            Point::default()
+        } else {
+            velocity
+        }
+    }
+
+    /// Abort the active touch session.
+    fn reset(&mut self) {
+        self.start = Default::default();
+        self.held_touch = None;
         self.is_drag = false;
+        self.slot = None;
+        self.cancel_velocity();
+    }
+
+    /// Start tracking a new touch point.
+    fn add_point(&mut self, slot: TouchSlot, position: Point<f64, Logical>) {
+        self.points.retain(|(point_slot, _)| *point_slot != slot);
+        self.points.push((slot, position));
+        self.restart_multi_touch();
+    }
+
+    /// Update an active touch point's position.
+    fn move_point(&mut self, slot: TouchSlot, position: Point<f64, Logical>) {
+        if let Some((_, point)) = self.points.iter_mut().find(|(point_slot, _)| *point_slot == slot)
+        {
+            *point = position;
+        }
+    }
+
+    /// Stop tracking a touch point.
+    fn remove_point(&mut self, slot: TouchSlot) {
+        self.points.retain(|(point_slot, _)| *point_slot != slot);
+        self.restart_multi_touch();
+    }
+
+    /// Restart multi-finger gesture recognition after the finger count changed.
+    fn restart_multi_touch(&mut self) {
+        match &self.multi_touch {
+            // Ignore all touches of a recognized gesture until they're released.
+            Some(multi_touch) if multi_touch.recognized && !self.points.is_empty() => (),
+            _ => {
+                let multi_touch = self.points.len() >= 2;
+                self.multi_touch = multi_touch.then(|| MultiTouch::new(&self.points));
+            },
+        }
+    }
+
+    /// Bounds of the indicator for an active back gesture.
+    ///
+    /// The indicator grows from the edge until the gesture is completed.
+    pub fn back_indicator(
+        &self,
+        output: &Output,
+        config: &GestureConfig,
+    ) -> Option<Rectangle<i32, Logical>> {
+        let edge = self
+            .start
+            .gesture
+            .filter(|edge| self.touching() && edge.action(&config.edges) == GestureAction::Back)?;
+
+        // Get completion progress along the swipe direction.
+        let output_size = output.size().to_f64();
+        let travel = self.position - self.start.position;
+        let (dx, dy) = edge.direction();
+        let progress = |travel: f64, direction: f64, length: f64| {
+            let distance = length * EDGE_SWIPE_PERCENTAGE;
+            if direction == 0. {
+                1.
+            } else {
+                (travel * direction / distance).clamp(0., 1.)
+            }
+        };
+        let progress =
+            progress(travel.x, dx, output_size.w).min(progress(travel.y, dy, output_size.h));
+        let width = BACK_INDICATOR_WIDTH * progress;
+        if width < 1. {
+            return None;
+        }
+
+        // Place the indicator at the edge, centered on the touch point.
+        let (loc, size) = if dx != 0. {
+            let x = if dx > 0. { 0. } else { output_size.w - width };
+            ((x, self.position.y - BACK_INDICATOR_LENGTH / 2.), (width, BACK_INDICATOR_LENGTH))
+        } else {
+            let y = if dy > 0. { 0. } else { output_size.h - width };
+            ((self.position.x - BACK_INDICATOR_LENGTH / 2., y), (BACK_INDICATOR_LENGTH, width))
+        };
+
+        Some(Rectangle::from_loc_and_size(loc, size).to_i32_round())
+    }
+
+    /// Check if a touch in a gesture zone can no longer complete its gesture.
+    ///
+    /// Touches moving along or away from the gesture's edge are not considered
+    /// gestures anymore.
+    fn gesture_rejected(&self, position: Point<f64, Logical>) -> bool {
+        let edge = match self.start.gesture {
+            Some(edge) => edge,
+            None => return false,
+        };
+
+        let travel = position - self.start.position;
+        if f64::sqrt(travel.x.powi(2) + travel.y.powi(2)) <= MAX_TAP_DISTANCE {
+            return false;
+        }
+
+        let (dx, dy) = edge.direction();
+        travel.x * dx + travel.y * dy <= 0.
     }
 
     /// Check if there's any touch velocity present.
@@ -105,24 +314,28 @@ impl TouchState {
     }
 
     /// Get the updated active touch action.
-    fn action(&mut self, output: &Output, overview_active: bool) -> Option<TouchAction> {
+    fn action(
+        &mut self,
+        output: &Output,
+        config: &GestureConfig,
+        overview_active: bool,
+    ) -> Option<TouchAction> {
         let output_size = output.size().to_f64();
         let touching = self.touching();
-        match self.start.gesture {
-            Some(Gesture::Overview) if overview_active => (),
-            Some(gesture) => {
-                if !touching && gesture.end_rect(output_size).contains(self.position) {
-                    return Some(TouchAction::Gesture(gesture));
-                }
-            },
-            _ => (),
+
+        // Ignore overview gestures while the overview is already open.
+        let overview = |edge: &Edge| edge.action(&config.edges) == GestureAction::Overview;
+        if let Some(edge) = self.start.gesture.filter(|edge| !overview_active || !overview(edge)) {
+            if !touching && edge.end_rect(output_size).contains(self.position) {
+                return Some(TouchAction::Gesture(edge));
+            }
         }
 
         // Convert to drag as soon as distance/time was exceeded once.
         let delta = self.start.position - self.position;
         if self.is_drag
             || f64::sqrt(delta.x.powi(2) + delta.y.powi(2)) > MAX_TAP_DISTANCE
-            || self.start.time.elapsed() >= HOLD_DURATION
+            || clock::elapsed(self.start.time) >= HOLD_DURATION
         {
             self.is_drag = true;
             return self.start.gesture.is_none().then(|| TouchAction::Drag);
@@ -135,91 +348,336 @@ impl TouchState {
 /// Start of a touch interaction.
 struct TouchStart {
     position: Point<f64, Logical>,
-    gesture: Option<Gesture>,
+    gesture: Option<Edge>,
     time: Instant,
 }
 
 impl Default for TouchStart {
     fn default() -> Self {
-        Self { time: Instant::now(), position: Default::default(), gesture: Default::default() }
+        Self { time: clock::now(), position: Default::default(), gesture: Default::default() }
     }
 }
 
 impl TouchStart {
-    fn new(output: &Output, position: Point<f64, Logical>) -> Self {
-        Self { gesture: Gesture::from_start(output, position), time: Instant::now(), position }
+    fn new(output: &Output, config: &GestureConfig, position: Point<f64, Logical>) -> Self {
+        let gesture = Edge::from_start(output, config, position);
+        Self { gesture, time: clock::now(), position }
+    }
+}
+
+/// Client touch events held back until a gesture was rejected.
+struct HeldTouch {
+    surface: OffsetSurface,
+    events: Vec<TouchEvent>,
+}
+
+/// Multi-finger gesture recognition.
+struct MultiTouch {
+    /// Touch points at the time the current finger count was reached.
+    start: Vec<(TouchSlot, Point<f64, Logical>)>,
+    recognized: bool,
+}
+
+impl MultiTouch {
+    fn new(points: &[(TouchSlot, Point<f64, Logical>)]) -> Self {
+        Self { start: points.to_vec(), recognized: false }
+    }
+
+    /// Get the name and action of the gesture matching the touch points'
+    /// movement.
+    fn action(
+        &self,
+        config: &GestureConfig,
+        points: &[(TouchSlot, Point<f64, Logical>)],
+    ) -> Option<(&'static str, GestureAction)> {
+        let start: Vec<_> = self.start.iter().map(|(_, position)| *position).collect();
+        let current: Vec<_> = self
+            .start
+            .iter()
+            .filter_map(|(slot, _)| points.iter().find(|(point_slot, _)| point_slot == slot))
+            .map(|(_, position)| *position)
+            .collect();
+
+        let (start_center, start_spread) = center_and_spread(&start);
+        let (center, spread) = center_and_spread(&current);
+        let delta = center - start_center;
+        let scale = spread / start_spread;
+
+        let fingers = self.start.len() as u32;
+        let min_swipe_distance = match fingers {
+            2 => MIN_TWO_FINGER_SWIPE_DISTANCE,
+            _ => MIN_MULTI_TOUCH_SWIPE_DISTANCE,
+        };
+
+        let (name, binding) = if delta.x.abs().max(delta.y.abs()) >= min_swipe_distance {
+            if delta.x.abs() >= delta.y.abs() {
+                if delta.x < 0. {
+                    ("gestures.swipe_left", config.swipe_left)
+                } else {
+                    ("gestures.swipe_right", config.swipe_right)
+                }
+            } else if delta.y < 0. {
+                ("gestures.swipe_up", config.swipe_up)
+            } else {
+                ("gestures.swipe_down", config.swipe_down)
+            }
+        } else if scale <= 1. - MIN_MULTI_TOUCH_PINCH_SCALE {
+            ("gestures.pinch_in", config.pinch_in)
+        } else if scale >= 1. + MIN_MULTI_TOUCH_PINCH_SCALE {
+            ("gestures.pinch_out", config.pinch_out)
+        } else {
+            return None;
+        };
+
+        let bound = binding.fingers == fingers && binding.action != GestureAction::None;
+        bound.then(|| (name, binding.action))
     }
 }
 
 /// Available touch input actions.
 #[derive(Debug, Copy, Clone)]
 enum TouchAction {
-    Gesture(Gesture),
+    Gesture(Edge),
     Drag,
     Tap,
 }
 
-/// Touch gestures.
+/// Navigation gestures.
 #[derive(Debug, Copy, Clone)]
 pub enum Gesture {
     Overview,
     Home,
 }
 
-impl Gesture {
-    /// Create a gesture from its starting location.
-    fn from_start(output: &Output, position: Point<f64, Logical>) -> Option<Self> {
-        let match_gesture =
-            |gesture: Gesture| gesture.start_rect(output).contains(position).then(|| gesture);
-        match_gesture(Gesture::Overview).or_else(|| match_gesture(Gesture::Home))
+/// Output edges and corners used as gesture zones.
+///
+/// Edges are relative to the output's orientation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Edge {
+    /// All gesture zones, with corners taking precedence over edges.
+    const ALL: [Edge; 8] = [
+        Edge::TopLeft,
+        Edge::TopRight,
+        Edge::BottomLeft,
+        Edge::BottomRight,
+        Edge::Top,
+        Edge::Bottom,
+        Edge::Left,
+        Edge::Right,
+    ];
+
+    /// Find the bound gesture zone containing a touch start location.
+    fn from_start(
+        output: &Output,
+        config: &GestureConfig,
+        position: Point<f64, Logical>,
+    ) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .filter(|edge| edge.action(&config.edges) != GestureAction::None)
+            .find(|edge| edge.start_rect(output, config).contains(position))
     }
 
-    /// Touch area expected for gesture initiation.
-    fn start_rect(&self, output: &Output) -> Rectangle<f64, Logical> {
-        let output_size = output.size().to_f64();
+    /// Action bound to this zone.
+    fn action(&self, bindings: &EdgeBindings) -> GestureAction {
         match self {
-            Gesture::Overview => {
-                let accuracy = OVERVIEW_GESTURE_ACCURACY / output.scale();
-                let loc = (output_size.w - accuracy, output_size.h - accuracy);
-                Rectangle::from_loc_and_size(loc, output_size)
-            },
-            Gesture::Home => {
-                let accuracy = HOME_GESTURE_ACCURACY / output.scale();
-                let loc = (output_size.w * HOME_WIDTH_PERCENTAGE, output_size.h - accuracy);
-                let size = (output_size.w - 2. * loc.0, output_size.h);
-                Rectangle::from_loc_and_size(loc, size)
-            },
+            Edge::Top => bindings.top,
+            Edge::Bottom => bindings.bottom,
+            Edge::Left => bindings.left,
+            Edge::Right => bindings.right,
+            Edge::TopLeft => bindings.top_left,
+            Edge::TopRight => bindings.top_right,
+            Edge::BottomLeft => bindings.bottom_left,
+            Edge::BottomRight => bindings.bottom_right,
         }
     }
 
+    /// Configuration key of this zone's binding.
+    fn name(&self) -> &'static str {
+        match self {
+            Edge::Top => "gestures.edges.top",
+            Edge::Bottom => "gestures.edges.bottom",
+            Edge::Left => "gestures.edges.left",
+            Edge::Right => "gestures.edges.right",
+            Edge::TopLeft => "gestures.edges.top_left",
+            Edge::TopRight => "gestures.edges.top_right",
+            Edge::BottomLeft => "gestures.edges.bottom_left",
+            Edge::BottomRight => "gestures.edges.bottom_right",
+        }
+    }
+
+    /// Inward direction of swipes starting in this zone.
+    fn direction(&self) -> (f64, f64) {
+        match self {
+            Edge::Top => (0., 1.),
+            Edge::Bottom => (0., -1.),
+            Edge::Left => (1., 0.),
+            Edge::Right => (-1., 0.),
+            Edge::TopLeft => (1., 1.),
+            Edge::TopRight => (-1., 1.),
+            Edge::BottomLeft => (1., -1.),
+            Edge::BottomRight => (-1., -1.),
+        }
+    }
+
+    /// Touch area expected for gesture initiation.
+    ///
+    /// Zones extend past the output to accept touches on its border.
+    fn start_rect(&self, output: &Output, config: &GestureConfig) -> Rectangle<f64, Logical> {
+        let output_size = output.size().to_f64();
+        let corner = config.corner_size / output.scale();
+        let edge = config.edge_size / output.scale();
+        let inset_x = output_size.w * config.edge_inset_percentage;
+        let inset_y = output_size.h * config.edge_inset_percentage;
+
+        let right = output_size.w - edge;
+        let bottom = output_size.h - edge;
+        let horizontal_size = (output_size.w - 2. * inset_x, 2. * edge);
+        let vertical_size = (2. * edge, output_size.h - 2. * inset_y);
+        let corner_size = (2. * corner, 2. * corner);
+
+        let (loc, size) = match self {
+            Edge::Top => ((inset_x, -edge), horizontal_size),
+            Edge::Bottom => ((inset_x, bottom), horizontal_size),
+            Edge::Left => ((-edge, inset_y), vertical_size),
+            Edge::Right => ((right, inset_y), vertical_size),
+            Edge::TopLeft => ((-corner, -corner), corner_size),
+            Edge::TopRight => ((output_size.w - corner, -corner), corner_size),
+            Edge::BottomLeft => ((-corner, output_size.h - corner), corner_size),
+            Edge::BottomRight => ((output_size.w - corner, output_size.h - corner), corner_size),
+        };
+        Rectangle::from_loc_and_size(loc, size)
+    }
+
     /// Touch area expected for gesture completion.
     fn end_rect(&self, output_size: Size<f64, Logical>) -> Rectangle<f64, Logical> {
-        let size = match self {
-            Gesture::Overview => (output_size.w * 0.75, output_size.h * 0.75),
-            Gesture::Home => (output_size.w, output_size.h * 0.75),
+        let inset_x = output_size.w * EDGE_SWIPE_PERCENTAGE;
+        let inset_y = output_size.h * EDGE_SWIPE_PERCENTAGE;
+
+        let (left, right) = match self {
+            Edge::Left | Edge::TopLeft | Edge::BottomLeft => (inset_x, output_size.w),
+            Edge::Right | Edge::TopRight | Edge::BottomRight => (0., output_size.w - inset_x),
+            Edge::Top | Edge::Bottom => (0., output_size.w),
         };
-        Rectangle::from_loc_and_size((0., 0.), size)
+        let (top, bottom) = match self {
+            Edge::Top | Edge::TopLeft | Edge::TopRight => (inset_y, output_size.h),
+            Edge::Bottom | Edge::BottomLeft | Edge::BottomRight => (0., output_size.h - inset_y),
+            Edge::Left | Edge::Right => (0., output_size.h),
+        };
+
+        Rectangle::from_loc_and_size((left, top), (right - left, bottom - top))
+    }
+}
+
+/// Scroll input for both pointer axes.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct ScrollEvent {
+    pub time: u32,
+    pub source: ScrollSource,
+    /// Horizontal and vertical scroll distance.
+    pub amount: (Option<f64>, Option<f64>),
+    /// Horizontal and vertical scroll wheel steps.
+    pub discrete: (Option<f64>, Option<f64>),
+}
+
+/// Device which generated scroll input.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrollSource {
+    Wheel,
+    Finger,
+    Continuous,
+}
+
+/// Active touchpad gesture.
+#[derive(Debug, Copy, Clone)]
+enum TouchpadGesture {
+    Swipe { delta: Point<f64, Logical>, scrolling: bool },
+    Pinch { scale: f64 },
+}
+
+/// Hardware switches which can be bound to actions.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HardwareSwitch {
+    Lid,
+    TabletMode,
+    KeypadSlide,
+}
+
+impl From<Switch> for HardwareSwitch {
+    fn from(switch: Switch) -> Self {
+        match switch {
+            Switch::Lid => Self::Lid,
+            Switch::TabletMode => Self::TabletMode,
+        }
+    }
+}
+
+/// Hardware switch states.
+///
+/// Switch states are unknown until their first toggle.
+#[derive(Default, Debug)]
+pub struct Switches {
+    lid: Option<bool>,
+    tablet_mode: Option<bool>,
+    keypad_slide: Option<bool>,
+
+    /// Actions bound to the current switch states.
+    actions: Vec<SwitchAction>,
+}
+
+impl Switches {
+    /// Check if a switch action is currently applied.
+    pub fn active(&self, action: SwitchAction) -> bool {
+        self.actions.contains(&action)
     }
 }
 
+/// Keyboard shortcuts for the docked layout.
+#[derive(Debug, Copy, Clone)]
+enum DesktopShortcut {
+    /// Move the focused window between the internal output and the desktop.
+    MoveWindow,
+    FocusNext,
+    FocusPrevious,
+    CloseWindow,
+}
+
 /// Generic touch event.
 #[derive(Copy, Clone, Debug)]
-struct TouchEvent {
-    position: Point<f64, Logical>,
-    ty: TouchEventType,
-    slot: TouchSlot,
-    time: u32,
+pub struct TouchEvent {
+    pub position: Point<f64, Logical>,
+    pub ty: TouchEventType,
+    pub slot: TouchSlot,
+    pub time: u32,
 }
 
 impl TouchEvent {
-    fn new(ty: TouchEventType, slot: TouchSlot, time: u32, position: Point<f64, Logical>) -> Self {
+    pub fn new(
+        ty: TouchEventType,
+        slot: TouchSlot,
+        time: u32,
+        position: Point<f64, Logical>,
+    ) -> Self {
         Self { slot, time, position, ty }
     }
 }
 
 /// Types of touch event.
 #[derive(Copy, Clone, Debug)]
-enum TouchEventType {
+pub enum TouchEventType {
     Down,
     Up,
     Motion,
@@ -228,6 +686,18 @@ enum TouchEventType {
 impl<B: Backend> Catacomb<B> {
     /// Process device orientation changes.
     pub fn handle_orientation(&mut self, orientation: Orientation) {
+        self.requested_orientation = orientation;
+
+        // Stay in landscape mode while it's forced by a switch.
+        let orientation = match orientation {
+            Orientation::Portrait | Orientation::InversePortrait
+                if self.switches.active(SwitchAction::Landscape) =>
+            {
+                Orientation::Landscape
+            },
+            orientation => orientation,
+        };
+
         self.output.set_orientation(orientation);
         self.windows.update_orientation(&mut self.output);
     }
@@ -249,15 +719,53 @@ impl<B: Backend> Catacomb<B> {
     /// Process new input events.
     pub fn handle_input<I: InputBackend>(&mut self, event: InputEvent<I>) {
         match event {
+            InputEvent::DeviceAdded { device } => self.on_device_added(device),
+            InputEvent::DeviceRemoved { device } => self.on_device_removed(device),
+            // Ignore absolute input mapped to other outputs.
+            InputEvent::PointerMotionAbsolute { event } if !self.maps_to_internal(&event) => (),
+            InputEvent::TouchDown { event } if !self.maps_to_internal(&event) => (),
+            InputEvent::TouchUp { event } if !self.maps_to_internal(&event) => (),
+            InputEvent::TouchMotion { event } if !self.maps_to_internal(&event) => (),
+            InputEvent::TouchFrame { event } if !self.maps_to_internal(&event) => (),
+            InputEvent::TouchCancel { event } if !self.maps_to_internal(&event) => (),
             InputEvent::Keyboard { event, .. } => self.on_keyboard_input(event),
+            InputEvent::PointerMotion { event } => self.on_pointer_motion(event),
+            InputEvent::PointerButton { event } if !self.touch_emulation => {
+                self.on_pointer_button(event.button_code(), event.state(), Event::time(&event));
+            },
+            InputEvent::PointerAxis { event } => self.on_pointer_axis(event),
+            InputEvent::GestureSwipeBegin { event } => {
+                self.on_touchpad_swipe_begin(event.fingers());
+            },
+            InputEvent::GestureSwipeUpdate { event } => {
+                self.on_touchpad_swipe_update((event.delta_x(), event.delta_y()).into());
+            },
+            InputEvent::GesturePinchBegin { event } => {
+                self.on_touchpad_pinch_begin(event.fingers());
+            },
+            InputEvent::GesturePinchUpdate { event } => {
+                self.on_touchpad_pinch_update(event.scale());
+            },
+            InputEvent::GestureSwipeEnd { event } => {
+                self.on_touchpad_gesture_end(event.cancelled());
+            },
+            InputEvent::GesturePinchEnd { event } => {
+                self.on_touchpad_gesture_end(event.cancelled());
+            },
+            InputEvent::PointerMotionAbsolute { event } if !self.touch_emulation => {
+                let position = self.transform_position(&event);
+                self.on_pointer_move(position, Event::time(&event));
+            },
             InputEvent::PointerButton { event } if event.button() == Some(MouseButton::Left) => {
                 let slot = TouchSlot::from(POINTER_TOUCH_SLOT);
                 let position = self.touch_state.position;
-                if event.state() == ButtonState::Pressed {
-                    self.on_touch_down(TouchEvent::new(TouchEventType::Down, slot, 0, position));
-                } else {
-                    self.on_touch_up(TouchEvent::new(TouchEventType::Up, slot, 0, position));
-                }
+                let event_type = match event.state() {
+                    ButtonState::Pressed => TouchEventType::Down,
+                    ButtonState::Released => TouchEventType::Up,
+                };
+                let time = Event::time(&event);
+                self.queue_touch_event(TouchEvent::new(event_type, slot, time, position));
+                self.on_touch_frame();
             },
             InputEvent::PointerMotionAbsolute { event } => {
                 let position = self.transform_position(&event);
@@ -265,89 +773,483 @@ impl<B: Backend> Catacomb<B> {
 
                 if self.touch_state.slot.is_some() {
                     let slot = TouchSlot::from(POINTER_TOUCH_SLOT);
-                    self.on_touch_motion(TouchEvent::new(TouchEventType::Down, slot, 0, position));
+                    let time = Event::time(&event);
+                    let event_type = TouchEventType::Motion;
+                    self.queue_touch_event(TouchEvent::new(event_type, slot, time, position));
+                    self.on_touch_frame();
                 }
             },
             InputEvent::TouchDown { event } => {
                 let position = self.transform_position(&event);
                 let event_type = TouchEventType::Down;
                 let event = TouchEvent::new(event_type, event.slot(), event.time(), position);
-                self.touch_state.events.push(event);
+                self.queue_touch_event(event);
             },
             InputEvent::TouchUp { event } => {
                 let position = self.touch_state.position;
                 let event_type = TouchEventType::Up;
                 let event = TouchEvent::new(event_type, event.slot(), event.time(), position);
-                self.touch_state.events.push(event);
+                self.queue_touch_event(event);
             },
             InputEvent::TouchMotion { event } => {
                 let position = self.transform_position(&event);
                 let event_type = TouchEventType::Motion;
                 let event = TouchEvent::new(event_type, event.slot(), event.time(), position);
-                self.touch_state.events.push(event);
+                self.queue_touch_event(event);
             },
-            // Apply all pending touch events.
-            InputEvent::TouchFrame { .. } => {
-                for i in 0..self.touch_state.events.len() {
-                    let event = self.touch_state.events[i];
-                    match event.ty {
-                        TouchEventType::Down => self.on_touch_down(event),
-                        TouchEventType::Up => self.on_touch_up(event),
-                        TouchEventType::Motion => self.on_touch_motion(event),
+            InputEvent::TouchFrame { .. } => self.on_touch_frame(),
+            InputEvent::SwitchToggle { event } => self.on_switch_toggle(event),
+            // Handle gesture touch cancel for nested compositors.
+            InputEvent::TouchCancel { event } => self.on_touch_cancel(event.slot()),
+            _ => (),
+        };
+    }
+
+    /// Handle new input devices.
+    ///
+    /// This tracks external keyboards and pointers for the docked layout.
+    fn on_device_added(&mut self, device: impl Device) {
+        let udev_device = device.syspath().and_then(|path| udev::Device::from_syspath(&path).ok());
+
+        let name = device.name();
+        if let Some(config) = device_config(&self.config.devices, &name, udev_device.as_ref()) {
+            debug!("Using device config for {name:?}: {config:?}");
+            self.device_configs.insert(device.id(), config.clone());
+        }
+
+        if udev_device.map_or(false, |udev_device| is_external_peripheral(&udev_device)) {
+            debug!("Added external input device {:?}", device.name());
+            self.add_peripheral(device.id());
+        }
+    }
+
+    /// Stop tracking removed input devices.
+    fn on_device_removed(&mut self, device: impl Device) {
+        let id = device.id();
+        self.device_configs.remove(&id);
+        self.remove_peripheral(&id);
+    }
+
+    /// Check if absolute input of a device is mapped to the internal output.
+    fn maps_to_internal<I: InputBackend>(&self, event: &impl Event<I>) -> bool {
+        let config = self.device_configs.get(&event.device().id());
+        match config.and_then(|config| config.internal_output.as_ref()) {
+            Some(output) => *output == self.output.name(),
+            None => true,
+        }
+    }
+
+    /// Handle lid and tablet-mode switch changes.
+    fn on_switch_toggle<I: InputBackend>(&mut self, event: impl SwitchToggleEvent<I>) {
+        if let Some(switch) = event.switch() {
+            self.on_switch(switch.into(), event.state() == SwitchState::On);
+        }
+    }
+
+    /// Update the state of a hardware switch.
+    pub fn on_switch(&mut self, switch: HardwareSwitch, on: bool) {
+        self.record_input(|_| InputRecord::Switch { switch, on });
+
+        match switch {
+            HardwareSwitch::Lid => self.switches.lid = Some(on),
+            HardwareSwitch::TabletMode => self.switches.tablet_mode = Some(on),
+            HardwareSwitch::KeypadSlide => self.switches.keypad_slide = Some(on),
+        }
+
+        debug!("Switch {switch:?} toggled: {on}");
+
+        self.update_switch_actions();
+    }
+
+    /// Apply the actions bound to the current switch states.
+    pub fn update_switch_actions(&mut self) {
+        let config = &self.config.switches;
+        let bindings = [
+            (self.switches.lid, &config.lid),
+            (self.switches.tablet_mode, &config.tablet_mode),
+            (self.switches.keypad_slide, &config.keypad_slide),
+        ];
+        let actions =
+            bindings.into_iter().flat_map(|(state, binding): (_, &SwitchBinding)| match state {
+                Some(true) => binding.on.as_slice(),
+                Some(false) => binding.off.as_slice(),
+                None => &[],
+            });
+        let actions = actions.copied().collect();
+
+        let old_actions = mem::replace(&mut self.switches.actions, actions);
+        let changed = |action| old_actions.contains(&action) != self.switches.active(action);
+        let lock = changed(SwitchAction::Lock) && self.switches.active(SwitchAction::Lock);
+        let blank = changed(SwitchAction::Blank);
+        let disable_osk = changed(SwitchAction::DisableOsk);
+        let landscape = changed(SwitchAction::Landscape);
+
+        if lock {
+            match &config.lock_command {
+                Some(command) => {
+                    if let Err(err) = daemon::spawn(command, &config.lock_args) {
+                        error!("Unable to spawn {command:?}: {err}");
                     }
+                },
+                None => warn!("Ignoring lock switch action without lock_command"),
+            }
+        }
+
+        if blank {
+            let blanked = self.switches.active(SwitchAction::Blank);
+            info!("Output blanking: {blanked}");
+
+            self.backend.set_internal_power(!blanked);
+            self.windows.damage_all();
+
+            if blanked {
+                // Touch input is ignored while blanked, so end all active touch sequences.
+                let slots: Vec<_> = self.touch_state.points.iter().map(|(slot, _)| *slot).collect();
+                for slot in slots {
+                    self.on_touch_cancel(slot);
                 }
-                self.touch_state.events.clear();
+
+                // No frames are rendered for captures while blanked.
+                self.fail_captures("output is blanked");
+            }
+        }
+
+        // Update text input focus to show or hide the on-screen keyboard.
+        if disable_osk {
+            let focus = self.last_focus.clone();
+            self.focus(focus.as_ref());
+        }
+
+        if landscape {
+            self.handle_orientation(self.requested_orientation);
+        }
+    }
+
+    /// Handle relative pointer motion.
+    ///
+    /// While docked, the pointer is confined to the desktop output.
+    fn on_pointer_motion<I: InputBackend>(&mut self, event: impl PointerMotionEvent<I>) {
+        let size = self.desktop_output().unwrap_or(&self.output).size().to_f64();
+
+        let mut position = self.cursor.position + event.delta();
+        position.x = position.x.clamp(0., size.w);
+        position.y = position.y.clamp(0., size.h);
+
+        self.on_pointer_move(position, Event::time(&event));
+    }
+
+    /// Move the pointer to a new location.
+    pub fn on_pointer_move(&mut self, position: Point<f64, Logical>, time: u32) {
+        let (x, y) = position.into();
+        self.record_input(|_| InputRecord::PointerMotion { time, x, y });
+
+        self.cursor.show();
+        self.cursor.set_position(position);
+
+        // Notify client.
+        let focus = self.windows.pointer_surface_at(position, self.docked());
+        let focus = focus.map(|OffsetSurface { surface, offset }| (surface, offset));
+        let serial = SERIAL_COUNTER.next_serial();
+        self.pointer.motion(position, focus, serial, time);
+    }
+
+    /// Handle pointer button presses and releases.
+    pub fn on_pointer_button(&mut self, button: u32, state: ButtonState, time: u32) {
+        let pressed = state == ButtonState::Pressed;
+        self.record_input(|_| InputRecord::PointerButton { button, pressed, time });
+
+        let state = match state {
+            ButtonState::Pressed => {
+                // Focus the window below the pointer.
+                let position = self.cursor.position;
+                let focus = if self.docked() {
+                    self.windows.desktop_surface_at(position)
+                } else {
+                    self.windows.touch_surface_at(position)
+                };
+
+                // Ensure the button is delivered to the surface which was clicked.
+                let focus = focus.map(|OffsetSurface { surface, offset }| (surface, offset));
+                self.pointer.motion(position, focus, SERIAL_COUNTER.next_serial(), time);
+
+                wl_pointer::ButtonState::Pressed
             },
-            // Handle gesture touch cancel for nested compositors.
-            InputEvent::TouchCancel { event } => {
-                self.touch_state.events.retain(|touch_event| touch_event.slot != event.slot());
+            ButtonState::Released => wl_pointer::ButtonState::Released,
+        };
+
+        // Notify client.
+        let serial = SERIAL_COUNTER.next_serial();
+        self.pointer.button(button, state, serial, time);
+    }
+
+    /// Handle scroll wheel and touchpad scrolling.
+    fn on_pointer_axis<I: InputBackend>(&mut self, event: impl PointerAxisEvent<I>) {
+        let source = match event.source() {
+            AxisSource::Continuous => ScrollSource::Continuous,
+            AxisSource::Finger => ScrollSource::Finger,
+            AxisSource::Wheel | AxisSource::WheelTilt => ScrollSource::Wheel,
+        };
+
+        self.on_scroll(ScrollEvent {
+            source,
+            time: Event::time(&event),
+            amount: (event.amount(Axis::Horizontal), event.amount(Axis::Vertical)),
+            discrete: (
+                event.amount_discrete(Axis::Horizontal),
+                event.amount_discrete(Axis::Vertical),
+            ),
+        });
+    }
+
+    /// Send scroll input to the focused client.
+    pub fn on_scroll(&mut self, event: ScrollEvent) {
+        self.record_input(|_| InputRecord::PointerAxis(event));
+
+        let source = match event.source {
+            ScrollSource::Continuous => wl_pointer::AxisSource::Continuous,
+            ScrollSource::Finger => wl_pointer::AxisSource::Finger,
+            ScrollSource::Wheel => wl_pointer::AxisSource::Wheel,
+        };
+
+        let mut frame = AxisFrame::new(event.time).source(source);
+        for (amount, discrete, wl_axis) in [
+            (event.amount.0, event.discrete.0, wl_pointer::Axis::HorizontalScroll),
+            (event.amount.1, event.discrete.1, wl_pointer::Axis::VerticalScroll),
+        ] {
+            // Wheels without continuous values scroll by three lines per step.
+            let amount = amount.or_else(|| discrete.map(|steps| steps * 3.));
+
+            match amount {
+                Some(amount) if amount != 0. => {
+                    frame = frame.value(wl_axis, amount);
+                    if let Some(discrete) = discrete {
+                        frame = frame.discrete(wl_axis, discrete as i32);
+                    }
+                },
+                // Signal end of kinetic scrolling.
+                _ if event.source == ScrollSource::Finger => frame = frame.stop(wl_axis),
+                _ => (),
+            }
+        }
+
+        self.pointer.axis(frame);
+    }
+
+    /// Start tracking a touchpad swipe.
+    pub fn on_touchpad_swipe_begin(&mut self, fingers: u32) {
+        self.record_input(|_| InputRecord::TouchpadSwipeBegin { fingers });
+
+        let gesture = TouchpadGesture::Swipe { delta: Default::default(), scrolling: false };
+        self.on_touchpad_gesture_begin(fingers, gesture);
+    }
+
+    /// Start tracking a touchpad pinch.
+    pub fn on_touchpad_pinch_begin(&mut self, fingers: u32) {
+        self.record_input(|_| InputRecord::TouchpadPinchBegin { fingers });
+
+        self.on_touchpad_gesture_begin(fingers, TouchpadGesture::Pinch { scale: 1. });
+    }
+
+    /// Start tracking a touchpad gesture.
+    ///
+    /// Gestures with a finger count other than the configured one are ignored.
+    fn on_touchpad_gesture_begin(&mut self, fingers: u32, gesture: TouchpadGesture) {
+        let bound = fingers == self.config.touchpad.fingers;
+        self.touch_state.touchpad_gesture = bound.then(|| gesture);
+    }
+
+    /// Handle touchpad swipe movement.
+    pub fn on_touchpad_swipe_update(&mut self, delta: Point<f64, Logical>) {
+        let (dx, dy) = delta.into();
+        self.record_input(|_| InputRecord::TouchpadSwipeUpdate { dx, dy });
+
+        let scroll = self.config.touchpad.scroll_overview && self.windows.overview_active();
+
+        let (total, scrolling) = match &mut self.touch_state.touchpad_gesture {
+            Some(TouchpadGesture::Swipe { delta, scrolling }) => (delta, scrolling),
+            _ => return,
+        };
+        *total += delta;
+
+        // Horizontal swipes scroll the overview like touch drags.
+        if scroll && total.x.abs() >= total.y.abs() {
+            *scrolling = true;
+            self.windows.scroll_overview(delta.x);
+        }
+    }
+
+    /// Handle touchpad pinch scale changes.
+    pub fn on_touchpad_pinch_update(&mut self, new_scale: f64) {
+        self.record_input(|_| InputRecord::TouchpadPinchUpdate { scale: new_scale });
+
+        if let Some(TouchpadGesture::Pinch { scale }) = &mut self.touch_state.touchpad_gesture {
+            *scale = new_scale;
+        }
+    }
+
+    /// Dispatch the action bound to a completed touchpad gesture.
+    pub fn on_touchpad_gesture_end(&mut self, cancelled: bool) {
+        self.record_input(|_| InputRecord::TouchpadGestureEnd { cancelled });
+
+        let config = self.config.touchpad;
+        let (name, action) = match self.touch_state.touchpad_gesture.take() {
+            Some(TouchpadGesture::Swipe { scrolling: true, .. }) => {
+                self.windows.on_drag_release(&self.output, &self.config.overview);
+                return;
             },
-            _ => (),
+            _ if cancelled => return,
+            Some(TouchpadGesture::Swipe { delta, .. }) => {
+                if delta.x.abs().max(delta.y.abs()) < MIN_TOUCHPAD_SWIPE_DISTANCE {
+                    return;
+                }
+
+                if delta.x.abs() >= delta.y.abs() {
+                    if delta.x < 0. {
+                        ("touchpad.swipe_left", config.swipe_left)
+                    } else {
+                        ("touchpad.swipe_right", config.swipe_right)
+                    }
+                } else if delta.y < 0. {
+                    ("touchpad.swipe_up", config.swipe_up)
+                } else {
+                    ("touchpad.swipe_down", config.swipe_down)
+                }
+            },
+            Some(TouchpadGesture::Pinch { scale }) if scale <= 1. - MIN_TOUCHPAD_PINCH_SCALE => {
+                ("touchpad.pinch_in", config.pinch_in)
+            },
+            Some(TouchpadGesture::Pinch { scale }) if scale >= 1. + MIN_TOUCHPAD_PINCH_SCALE => {
+                ("touchpad.pinch_out", config.pinch_out)
+            },
+            Some(TouchpadGesture::Pinch { .. }) | None => return,
         };
+
+        if action != GestureAction::None {
+            debug!("Completed touchpad gesture {name}: {action:?}");
+            self.on_gesture_action(action, name);
+        }
     }
 
-    /// Handle new touch input start.
-    fn on_touch_down(&mut self, event: TouchEvent) {
-        let TouchEvent { time, slot, position, .. } = event;
-        let surface = self.windows.touch_surface_at(event.position);
+    /// Queue a touch event until the next touch frame.
+    pub fn queue_touch_event(&mut self, event: TouchEvent) {
+        self.record_input(|recorder| recorder.touch_record(&event));
+
+        // Keep touches on a blanked screen away from clients.
+        if self.switches.active(SwitchAction::Blank) {
+            return;
+        }
+
+        self.touch_state.events.push(event);
+    }
+
+    /// Apply all pending touch events.
+    pub fn on_touch_frame(&mut self) {
+        self.record_input(|_| InputRecord::TouchFrame);
+
+        for i in 0..self.touch_state.events.len() {
+            let event = self.touch_state.events[i];
+            trace!("{event:?}");
+            match event.ty {
+                TouchEventType::Down => self.on_touch_down(event),
+                TouchEventType::Up => self.on_touch_up(event),
+                TouchEventType::Motion => self.on_touch_motion(event),
+            }
+        }
+        self.touch_state.events.clear();
+    }
+
+    /// Cancel a touch point.
+    ///
+    /// This discards its pending events and resets all touch state tracking it.
+    pub fn on_touch_cancel(&mut self, slot: TouchSlot) {
+        debug!("Touch cancelled for {slot:?}");
+        self.record_input(|recorder| InputRecord::TouchCancel { slot: recorder.slot_id(slot) });
+        self.touch_state.events.retain(|touch_event| touch_event.slot != slot);
+        self.touch_state.remove_point(slot);
 
         // Notify client.
-        if let Some(OffsetSurface { surface, offset }) = surface {
-            let serial = SERIAL_COUNTER.next_serial();
-            self.touch_state.touch.down(serial, time, &surface, offset, slot, position);
+        self.touch_state.touch.cancel();
+
+        // Stop the active touch session without triggering any action.
+        if self.touch_state.slot == Some(slot) {
+            self.touch_state.reset();
+            self.windows.cancel_drag();
         }
+    }
+
+    /// Handle new touch input start.
+    fn on_touch_down(&mut self, event: TouchEvent) {
+        let TouchEvent { slot, position, .. } = event;
+        self.cursor.hide();
+        self.touch_state.add_point(slot, position);
+
+        let surface = self.windows.touch_surface_at(event.position);
 
         // Allow only a single touch at a time.
         if self.touch_state.slot.is_some() {
+            // Notify client.
+            if let Some(surface) = surface {
+                self.send_touch_down(&surface, event);
+            }
             return;
         }
         self.touch_state.slot = Some(slot);
 
         // Initialize the touch state.
-        self.touch_state.start(&self.output, position);
+        self.touch_state.start(&self.output, &self.config.gestures, event.time, position);
+
+        let gesture = self.touch_state.start.gesture;
+        debug!("Touch start for {slot:?} at {position:?}, gesture zone: {gesture:?}");
+
+        match surface {
+            // Hold back touches in gesture zones until the gesture is rejected.
+            Some(surface) if gesture.is_some() => {
+                self.touch_state.held_touch = Some(HeldTouch { surface, events: vec![event] });
+            },
+            // Notify client.
+            Some(surface) => self.send_touch_down(&surface, event),
+            None => (),
+        }
 
         // Only send touch start if there's no gesture in progress.
-        if self.touch_state.start.gesture.is_none() {
-            self.windows.on_touch_start(&self.output, position);
+        if gesture.is_none() {
+            self.windows.on_touch_start(&self.output, &self.config.overview, position);
         }
     }
 
     /// Handle touch input release.
     fn on_touch_up(&mut self, event: TouchEvent) {
-        // Notify client.
-        let serial = SERIAL_COUNTER.next_serial();
-        self.touch_state.touch.up(serial, event.time, event.slot);
+        self.touch_state.remove_point(event.slot);
 
         // Check if slot is the active one.
         if self.touch_state.slot != Some(event.slot) {
+            self.send_touch_up(event);
             return;
         }
         self.touch_state.slot = None;
 
+        self.touch_state.velocity =
+            self.touch_state.fling_velocity(&self.config.gestures, event.time);
+
         let overview_active = self.windows.overview_active();
-        match self.touch_state.action(&self.output, overview_active) {
+        let action = self.touch_state.action(&self.output, &self.config.gestures, overview_active);
+        debug!("Touch end for {:?} at {:?}: {action:?}", event.slot, self.touch_state.position);
+
+        // Notify client, unless its touch was held back for a completed gesture.
+        match self.touch_state.held_touch.take() {
+            Some(_) if matches!(action, Some(TouchAction::Gesture(_))) => (),
+            held_touch => {
+                if let Some(held_touch) = held_touch {
+                    self.replay_touch(held_touch);
+                }
+                self.send_touch_up(event);
+            },
+        }
+
+        match action {
             Some(TouchAction::Tap) => {
-                self.windows.on_tap(&self.output, self.touch_state.position);
+                let position = self.touch_state.position;
+                self.windows.on_tap(&self.output, &self.config.overview, position);
             },
             Some(TouchAction::Drag | TouchAction::Gesture(_)) => {
                 self.add_velocity_timeout();
@@ -359,18 +1261,67 @@ impl<B: Backend> Catacomb<B> {
 
     /// Handle touch input movement.
     fn on_touch_motion(&mut self, event: TouchEvent) {
-        // Notify client.
-        self.touch_state.touch.motion(event.time, event.slot, event.position);
+        // Notify client, unless the touch is held back for gesture recognition.
+        match &mut self.touch_state.held_touch {
+            Some(held_touch) if self.touch_state.slot == Some(event.slot) => {
+                held_touch.events.push(event);
+            },
+            _ => self.touch_state.touch.motion(event.time, event.slot, event.position),
+        }
+
+        self.touch_state.move_point(event.slot, event.position);
+        self.update_multi_touch();
 
         // Ignore anything but the active touch slot.
         if self.touch_state.slot != Some(event.slot) {
             return;
         }
 
-        self.touch_state.velocity = event.position - self.touch_state.position;
+        // Pass touches to clients once they can't complete their gesture anymore.
+        if self.touch_state.gesture_rejected(event.position) {
+            debug!("Gesture rejected for {:?} at {:?}", event.slot, event.position);
+
+            self.touch_state.start.gesture = None;
+            if let Some(held_touch) = self.touch_state.held_touch.take() {
+                self.replay_touch(held_touch);
+            }
+
+            let position = self.touch_state.start.position;
+            self.windows.on_touch_start(&self.output, &self.config.overview, position);
+        }
+
+        self.touch_state.push_history(event.time, event.position);
+        self.touch_state.velocity = self.touch_state.estimate_velocity();
         self.update_position(event.position);
     }
 
+    /// Send touch events held back during gesture recognition to the client.
+    fn replay_touch(&mut self, held_touch: HeldTouch) {
+        for event in held_touch.events {
+            match event.ty {
+                TouchEventType::Down => self.send_touch_down(&held_touch.surface, event),
+                TouchEventType::Motion => {
+                    self.touch_state.touch.motion(event.time, event.slot, event.position);
+                },
+                TouchEventType::Up => (),
+            }
+        }
+    }
+
+    /// Notify client about a new touch point.
+    fn send_touch_down(&mut self, surface: &OffsetSurface, event: TouchEvent) {
+        let TouchEvent { time, slot, position, .. } = event;
+        let OffsetSurface { surface, offset } = surface;
+        let serial = SERIAL_COUNTER.next_serial();
+        self.touch_state.touch.down(serial, time, surface, *offset, slot, position);
+    }
+
+    /// Notify client about a released touch point.
+    fn send_touch_up(&mut self, event: TouchEvent) {
+        let serial = SERIAL_COUNTER.next_serial();
+        self.touch_state.touch.up(serial, event.time, event.slot);
+    }
+
     /// Update the touch position.
     ///
     /// NOTE: This should be called after adding new timeouts to allow clearing
@@ -378,13 +1329,14 @@ impl<B: Backend> Catacomb<B> {
     /// actions.
     fn update_position(&mut self, position: Point<f64, Logical>) {
         let overview_active = self.windows.overview_active();
-        match self.touch_state.action(&self.output, overview_active) {
+        match self.touch_state.action(&self.output, &self.config.gestures, overview_active) {
             Some(TouchAction::Drag) => {
-                self.windows.on_drag(&self.output, &mut self.touch_state, position);
+                let config = &self.config.overview;
+                self.windows.on_drag(&self.output, config, &mut self.touch_state, position);
 
                 // Signal drag end once no more velocity is present.
                 if !self.touch_state.touching() && !self.touch_state.has_velocity() {
-                    self.windows.on_drag_release(&self.output);
+                    self.windows.on_drag_release(&self.output, &self.config.overview);
                 }
             },
             Some(TouchAction::Gesture(gesture)) => self.on_gesture(gesture),
@@ -394,18 +1346,90 @@ impl<B: Backend> Catacomb<B> {
         self.touch_state.position = position;
     }
 
-    /// Dispatch gestures if it was completed.
-    fn on_gesture(&mut self, gesture: Gesture) {
+    /// Dispatch multi-finger gestures once they were recognized.
+    fn update_multi_touch(&mut self) {
+        let multi_touch = match &mut self.touch_state.multi_touch {
+            Some(multi_touch) if !multi_touch.recognized => multi_touch,
+            _ => return,
+        };
+
+        let (name, action) =
+            match multi_touch.action(&self.config.gestures, &self.touch_state.points) {
+                Some(action) => action,
+                None => return,
+            };
+        multi_touch.recognized = true;
+
+        let fingers = multi_touch.start.len();
+        debug!("Completed {fingers}-finger gesture {name}: {action:?}");
+
+        // Stop single-touch handling for the gesture's touch points.
+        if self.touch_state.slot.take().is_some() {
+            self.windows.cancel_drag();
+        }
+        self.touch_state.held_touch = None;
+        self.touch_state.cancel_velocity();
+
+        // Notify client.
+        self.touch_state.touch.cancel();
+
+        self.on_gesture_action(action, name);
+    }
+
+    /// Run the action bound to a gesture.
+    ///
+    /// The gesture's name is its configuration key, which is sent to IPC
+    /// subscribers for the `ipc` action.
+    fn on_gesture_action(&mut self, action: GestureAction, name: &str) {
+        match action {
+            GestureAction::Overview => self.windows.on_gesture(&self.output, Gesture::Overview),
+            GestureAction::Home => self.windows.on_gesture(&self.output, Gesture::Home),
+            GestureAction::Next => self.windows.cycle_primary(&self.output, true),
+            GestureAction::Previous => self.windows.cycle_primary(&self.output, false),
+            GestureAction::Back => self.on_back(),
+            GestureAction::Launcher => spawn_launcher(&self.config.launcher),
+            GestureAction::ToggleSecondary => self.windows.toggle_secondary(&self.output),
+            GestureAction::Ipc => {
+                let event = IpcEvent::GestureCompleted { gesture: name.into() };
+                self.ipc_subscribers.broadcast(&event);
+            },
+            GestureAction::None => (),
+        }
+    }
+
+    /// Dispatch edge gestures if it was completed.
+    fn on_gesture(&mut self, edge: Edge) {
         // Only accept gestures when the touch input was released.
         if self.touch_state.touching() {
             return;
         }
 
-        self.windows.on_gesture(&self.output, gesture);
+        let action = edge.action(&self.config.gestures.edges);
+        debug!("Completed {edge:?} edge gesture: {action:?}");
+
         self.touch_state.timer.cancel_all_timeouts();
 
         // Notify client.
         self.touch_state.touch.cancel();
+
+        self.on_gesture_action(action, edge.name());
+    }
+
+    /// Navigate back in the focused window.
+    ///
+    /// Open popups are dismissed before the back key is sent to the window.
+    fn on_back(&mut self) {
+        if self.windows.dismiss_popup() {
+            return;
+        }
+
+        let keycode = self.config.gestures.back_key.keycode();
+        let time = self.windows.runtime();
+        for state in [KeyState::Pressed, KeyState::Released] {
+            let serial = SERIAL_COUNTER.next_serial();
+            self.keyboard
+                .input::<(), _>(keycode, state, serial, time, |_, _| FilterResult::Forward);
+        }
     }
 
     /// Process a single velocity tick.
@@ -414,14 +1438,17 @@ impl<B: Backend> Catacomb<B> {
         //
         // The animations are designed for 60FPS, but should still behave properly for
         // other refresh rates.
-        let velocity = &mut self.touch_state.velocity;
-        let position = &mut self.touch_state.position;
         let animation_speed = self.output.frame_interval() as f64 / 16.;
-        velocity.x -= velocity.x.signum()
-            * (velocity.x.abs() * FRICTION * animation_speed + 1.).min(velocity.x.abs());
-        velocity.y -= velocity.y.signum()
-            * (velocity.y.abs() * FRICTION * animation_speed + 1.).min(velocity.y.abs());
-        *position += *velocity * animation_speed;
+        let decay = (1. - self.config.gestures.friction).powf(animation_speed);
+        let decelerate = |velocity: f64| {
+            let speed = (velocity.abs() * decay - animation_speed).max(0.);
+            velocity.signum() * speed
+        };
+
+        let velocity = &mut self.touch_state.velocity;
+        velocity.x = decelerate(velocity.x);
+        velocity.y = decelerate(velocity.y);
+        self.touch_state.position += *velocity * animation_speed;
 
         // Request another callback.
         self.add_velocity_timeout();
@@ -441,29 +1468,62 @@ impl<B: Backend> Catacomb<B> {
 
     /// Handle new keyboard input events.
     fn on_keyboard_input<I: InputBackend>(&mut self, event: impl KeyboardKeyEvent<I>) {
-        let serial = SERIAL_COUNTER.next_serial();
         let time = Event::time(&event);
-        let keycode = event.key_code();
-        let state = event.state();
+        self.on_key(event.key_code(), event.state(), time);
+    }
+
+    /// Handle a single key press or release.
+    pub fn on_key(&mut self, keycode: u32, state: KeyState, time: u32) {
+        let pressed = state == KeyState::Pressed;
+        self.record_input(|_| InputRecord::Key { keycode, pressed, time });
 
-        self.keyboard.input(keycode, state, serial, time, |_modifiers, keysym| {
+        let serial = SERIAL_COUNTER.next_serial();
+        let docked = self.docked();
+
+        let shortcut = self.keyboard.input(keycode, state, serial, time, |modifiers, keysym| {
             match keysym.modified_sym() {
                 keysym @ keysyms::KEY_XF86Switch_VT_1..=keysyms::KEY_XF86Switch_VT_12 => {
                     let vt = (keysym - keysyms::KEY_XF86Switch_VT_1 + 1) as i32;
                     self.backend.change_vt(vt);
                 },
                 keysyms::KEY_XF86PowerOff if state == KeyState::Pressed => {
-                    let _ = Command::new(APP_DRAWER)
-                        .stdin(Stdio::null())
-                        .stdout(Stdio::null())
-                        .stderr(Stdio::null())
-                        .spawn();
+                    spawn_launcher(&self.config.launcher);
+                },
+                keysym if docked && modifiers.logo => {
+                    let shortcut = match keysym {
+                        keysyms::KEY_Return => DesktopShortcut::MoveWindow,
+                        keysyms::KEY_Right => DesktopShortcut::FocusNext,
+                        keysyms::KEY_Left => DesktopShortcut::FocusPrevious,
+                        keysyms::KEY_q => DesktopShortcut::CloseWindow,
+                        _ => return FilterResult::Forward,
+                    };
+                    return FilterResult::Intercept(Some(shortcut));
                 },
                 _ => return FilterResult::Forward,
             }
 
-            FilterResult::Intercept(())
+            FilterResult::Intercept(None)
         });
+
+        if let Some(shortcut) = shortcut.flatten().filter(|_| pressed) {
+            self.on_desktop_shortcut(shortcut);
+        }
+    }
+
+    /// Handle keyboard shortcuts of the docked layout.
+    fn on_desktop_shortcut(&mut self, shortcut: DesktopShortcut) {
+        debug!("Desktop shortcut {shortcut:?}");
+
+        match shortcut {
+            DesktopShortcut::MoveWindow => {
+                if let Some(desktop) = self.external_outputs.first() {
+                    self.windows.toggle_desktop(&self.output, &desktop.output);
+                }
+            },
+            DesktopShortcut::FocusNext => self.windows.cycle_desktop_focus(true),
+            DesktopShortcut::FocusPrevious => self.windows.cycle_desktop_focus(false),
+            DesktopShortcut::CloseWindow => self.windows.close_focused(),
+        }
     }
 
     /// Apply an output transform to a point.
@@ -472,9 +1532,21 @@ impl<B: Backend> Catacomb<B> {
         E: PositionEvent<I>,
         I: InputBackend,
     {
+        // Get position relative to the device's extents.
+        let (mut x, mut y) = event.position_transformed((1, 1).into()).into();
+
+        // Transform X/Y according to the device's configured rotation.
+        let config = self.device_configs.get(&event.device().id());
+        (x, y) = match config.map_or(0, |config| config.rotation % 360) {
+            90 => (1. - y, x),
+            180 => (1. - x, 1. - y),
+            270 => (y, 1. - x),
+            _ => (x, y),
+        };
+
         let screen_size = self.output.resolution();
-        let (mut x, mut y) = event.position_transformed(screen_size).into();
         let (width, height) = screen_size.to_f64().into();
+        (x, y) = (x * width, y * height);
 
         // Transform X/Y according to output rotation.
         (x, y) = match self.output.orientation() {
@@ -487,3 +1559,241 @@ impl<B: Backend> Catacomb<B> {
         (x, y).into()
     }
 }
+
+/// Center of touch points and their average distance to it.
+fn center_and_spread(points: &[Point<f64, Logical>]) -> (Point<f64, Logical>, f64) {
+    let count = points.len() as f64;
+    let sum = points.iter().fold(Point::default(), |sum, point| sum + *point);
+    let center = Point::from((sum.x / count, sum.y / count));
+
+    let distances = points.iter().map(|point| {
+        let delta = *point - center;
+        f64::sqrt(delta.x.powi(2) + delta.y.powi(2))
+    });
+    let spread = distances.sum::<f64>() / count;
+
+    (center, spread)
+}
+
+/// Start the configured launcher.
+fn spawn_launcher(launcher: &LauncherConfig) {
+    if let Err(err) = daemon::spawn(&launcher.command, &launcher.args) {
+        let command = &launcher.command;
+        error!("Unable to spawn {command:?}: {err}");
+    }
+}
+
+/// Find the first `[[devices]]` entry matching an input device.
+fn device_config<'a>(
+    configs: &'a [DeviceConfig],
+    name: &str,
+    udev_device: Option<&udev::Device>,
+) -> Option<&'a DeviceConfig> {
+    let udev_property = |key: &str| {
+        let value = udev_device?.property_value(key)?;
+        Some(value.to_string_lossy().into_owned())
+    };
+
+    configs.iter().find(|config| config.matches(name, udev_property))
+}
+
+/// Check if an input device is an external keyboard or pointer.
+///
+/// Built-in buttons are usually platform devices, while external keyboards and
+/// pointers are connected through USB or Bluetooth.
+fn is_external_peripheral(udev_device: &udev::Device) -> bool {
+    let property = |key: &str| udev_device.property_value(key).map(|value| value.to_string_lossy());
+
+    let external_bus = matches!(property("ID_BUS").as_deref(), Some("usb" | "bluetooth"));
+    let peripheral = ["ID_INPUT_KEYBOARD", "ID_INPUT_MOUSE", "ID_INPUT_TOUCHPAD"]
+        .into_iter()
+        .any(|key| property(key).as_deref() == Some("1"));
+
+    external_bus && peripheral
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::test_support::{ClientTouchEvent, TestCompositor};
+
+    /// Record 100ms of vertical touch movement at `speed` pixels per second,
+    /// sampled at `rate` Hz.
+    ///
+    /// Returns the timestamp of the last sample.
+    fn record_movement(touch_state: &mut TouchState, rate: u32, speed: f64) -> u32 {
+        touch_state.history.clear();
+
+        let mut time = 0;
+        for frame in 0..=rate / 10 {
+            time = frame * 1000 / rate;
+            touch_state.push_history(time, Point::from((0., speed * time as f64 / 1000.)));
+        }
+
+        time
+    }
+
+    #[test]
+    fn velocity_independent_of_refresh_rate() {
+        let mut test = TestCompositor::new();
+        let touch_state = &mut test.catacomb.touch_state;
+
+        // 600 pixels per second are 9.6 pixels per 16ms.
+        for rate in [60, 120] {
+            record_movement(touch_state, rate, 600.);
+            let velocity = touch_state.estimate_velocity();
+            assert_eq!(velocity.x, 0.);
+            assert!((velocity.y - 9.6).abs() < 1e-9, "{rate}Hz velocity: {velocity:?}");
+        }
+    }
+
+    #[test]
+    fn fling_velocity_threshold() {
+        let mut test = TestCompositor::new();
+        let config = test.catacomb.config.gestures;
+        let touch_state = &mut test.catacomb.touch_state;
+        let min_velocity = config.min_fling_velocity;
+
+        for rate in [60, 120] {
+            let end = record_movement(touch_state, rate, min_velocity * 2.);
+            assert_ne!(touch_state.fling_velocity(&config, end), Point::default());
+
+            let end = record_movement(touch_state, rate, min_velocity / 2.);
+            assert_eq!(touch_state.fling_velocity(&config, end), Point::default());
+
+            // Touch points which came to rest before release don't fling.
+            let end = record_movement(touch_state, rate, min_velocity * 2.);
+            assert_eq!(touch_state.fling_velocity(&config, end + 200), Point::default());
+        }
+    }
+
+    /// Create a compositor with a mapped window recording its touch events.
+    ///
+    /// The left edge is bound to the back gesture.
+    fn touch_client() -> (TestCompositor, Rc<RefCell<Vec<ClientTouchEvent>>>) {
+        let mut test = TestCompositor::new();
+        test.catacomb.config.gestures.edges.left = GestureAction::Back;
+        let toplevel = test.create_toplevel("test");
+        test.roundtrip();
+        test.attach_buffer(&toplevel);
+        let touch = test.bind_touch();
+
+        (test, touch)
+    }
+
+    #[test]
+    fn rejected_gesture_replays_touch() {
+        let (mut test, touch) = touch_client();
+
+        // Moving along the edge rejects the back gesture.
+        test.touch_down(0, (10., 360.));
+        test.touch_frame();
+        test.roundtrip();
+        assert!(touch.borrow().is_empty());
+
+        test.touch_motion(0, (10., 300.));
+        test.touch_frame();
+        test.roundtrip();
+
+        assert_eq!(touch.borrow().as_slice(), &[
+            ClientTouchEvent::Down { id: 0, position: (10., 360.) },
+            ClientTouchEvent::Motion { id: 0, position: (10., 300.) },
+        ]);
+    }
+
+    #[test]
+    fn completed_gesture_hides_touch() {
+        let (mut test, touch) = touch_client();
+
+        test.swipe((2., 360.), (200., 360.));
+        test.roundtrip();
+
+        assert!(touch.borrow().is_empty());
+    }
+
+    #[test]
+    fn tap_in_gesture_zone_replays_touch() {
+        let (mut test, touch) = touch_client();
+
+        test.touch_down(0, (10., 360.));
+        test.touch_frame();
+        test.touch_up(0);
+        test.touch_frame();
+        test.roundtrip();
+
+        assert_eq!(touch.borrow().as_slice(), &[
+            ClientTouchEvent::Down { id: 0, position: (10., 360.) },
+            ClientTouchEvent::Up { id: 0 },
+        ]);
+    }
+
+    #[test]
+    fn lid_switch_blanks_output() {
+        let mut test = TestCompositor::new();
+        test.catacomb.config.switches.lid.on = vec![SwitchAction::Blank];
+
+        test.catacomb.on_switch(HardwareSwitch::Lid, true);
+        assert!(test.catacomb.switches.active(SwitchAction::Blank));
+
+        // Captures fail instead of waiting for the next frame.
+        let result = Rc::new(RefCell::new(None));
+        let capture_result = result.clone();
+        test.catacomb.capture_frame("/tmp/catacomb-blanked.png".into(), move |result| {
+            *capture_result.borrow_mut() = Some(result);
+        });
+        assert!(matches!(*result.borrow(), Some(Err(_))));
+
+        // Touch input is held back.
+        test.touch_down(0, (100., 100.));
+        test.touch_frame();
+        assert!(test.catacomb.touch_state.points.is_empty());
+
+        test.catacomb.on_switch(HardwareSwitch::Lid, false);
+        assert!(!test.catacomb.switches.active(SwitchAction::Blank));
+    }
+
+    #[test]
+    fn tablet_mode_forces_landscape() {
+        let mut test = TestCompositor::new();
+        test.catacomb.config.switches.tablet_mode.on = vec![SwitchAction::Landscape];
+
+        test.catacomb.on_switch(HardwareSwitch::TabletMode, true);
+        assert_eq!(test.catacomb.output.orientation(), Orientation::Landscape);
+
+        // Device rotation is applied once the switch is released.
+        test.catacomb.handle_orientation(Orientation::InversePortrait);
+        assert_eq!(test.catacomb.output.orientation(), Orientation::Landscape);
+
+        test.catacomb.on_switch(HardwareSwitch::TabletMode, false);
+        assert_eq!(test.catacomb.output.orientation(), Orientation::InversePortrait);
+    }
+
+    #[test]
+    fn keypad_slide_disables_osk() {
+        let mut test = TestCompositor::new();
+
+        test.catacomb.on_switch(HardwareSwitch::KeypadSlide, true);
+        assert!(test.catacomb.switches.active(SwitchAction::DisableOsk));
+        assert_eq!(test.catacomb.output.orientation(), Orientation::Landscape);
+
+        test.catacomb.on_switch(HardwareSwitch::KeypadSlide, false);
+        assert!(!test.catacomb.switches.active(SwitchAction::DisableOsk));
+        assert_eq!(test.catacomb.output.orientation(), Orientation::Portrait);
+    }
+
+    #[test]
+    fn reloaded_bindings_apply_to_current_state() {
+        let mut test = TestCompositor::new();
+        test.catacomb.config.switches.lid.on = Vec::new();
+
+        test.catacomb.on_switch(HardwareSwitch::Lid, true);
+        assert!(!test.catacomb.switches.active(SwitchAction::DisableOsk));
+
+        test.catacomb.config.switches.lid.on = vec![SwitchAction::DisableOsk];
+        test.catacomb.update_switch_actions();
+        assert!(test.catacomb.switches.active(SwitchAction::DisableOsk));
+    }
+}
@@ -0,0 +1,252 @@
+//! IPC socket server.
+
+use std::error::Error;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+
+use calloop::generic::Generic;
+use calloop::{Interest, LoopHandle, Mode, PostAction};
+use catacomb_ipc::{IpcEvent, IpcMessage, IpcResponse, Orientation as IpcOrientation};
+use log::{error, warn};
+
+use crate::catacomb::{Backend, Catacomb};
+use crate::daemon;
+use crate::input::Gesture;
+use crate::orientation::Orientation;
+
+/// Maximum length of a single IPC message in bytes.
+const MAX_MESSAGE_LENGTH: usize = 64 * 1024;
+
+/// Create the IPC socket for a Wayland socket.
+///
+/// Returns the path of the created socket.
+pub fn spawn_ipc_socket<'a, B: Backend + 'static>(
+    loop_handle: LoopHandle<'a, Catacomb<B>>,
+    wayland_display: &str,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let socket_path =
+        catacomb_ipc::socket_path(wayland_display).ok_or("XDG_RUNTIME_DIR is not set")?;
+
+    // Remove stale sockets from previous sessions.
+    let _ = fs::remove_file(&socket_path);
+
+    let listener = UnixListener::bind(&socket_path)?;
+    listener.set_nonblocking(true)?;
+
+    let handle = loop_handle.clone();
+    let source = Generic::new(listener, Interest::READ, Mode::Level);
+    loop_handle
+        .insert_source(source, move |_, listener, _| {
+            match listener.accept() {
+                Ok((stream, _)) => add_client(&handle, stream),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => (),
+                Err(err) => error!("IPC socket error: {err}"),
+            }
+
+            Ok(PostAction::Continue)
+        })
+        .expect("insert IPC socket source");
+
+    Ok(socket_path)
+}
+
+/// Start listening for messages from a new IPC client.
+fn add_client<B: Backend + 'static>(loop_handle: &LoopHandle<'_, Catacomb<B>>, stream: UnixStream) {
+    if let Err(err) = stream.set_nonblocking(true) {
+        warn!("IPC client error: {err}");
+        return;
+    }
+
+    let mut buffer = Vec::new();
+    let source = Generic::new(stream, Interest::READ, Mode::Level);
+    let result = loop_handle.insert_source(source, move |_, stream, catacomb| {
+        let mut chunk = [0; 1024];
+        match stream.read(&mut chunk) {
+            Ok(0) => return Ok(PostAction::Remove),
+            Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(PostAction::Continue),
+            Err(_) => return Ok(PostAction::Remove),
+        }
+
+        // Process all complete messages.
+        while let Some(index) = buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=index).collect();
+            let response = match serde_json::from_slice(&line) {
+                Ok(message) => catacomb.handle_ipc_message(message, stream),
+                Err(err) => Some(IpcResponse::error(format!("invalid message: {err}"))),
+            };
+
+            // Responses for deferred messages are sent once they're processed.
+            let response = match response {
+                Some(response) => response,
+                None => continue,
+            };
+
+            if send_response(stream, &response).is_err() {
+                return Ok(PostAction::Remove);
+            }
+        }
+
+        // Disconnect clients which never terminate their message.
+        if buffer.len() > MAX_MESSAGE_LENGTH {
+            let error = format!("message exceeds {MAX_MESSAGE_LENGTH} bytes");
+            let _ = send_response(stream, &IpcResponse::error(error));
+            return Ok(PostAction::Remove);
+        }
+
+        Ok(PostAction::Continue)
+    });
+
+    if result.is_err() {
+        error!("IPC client error: unable to register client");
+    }
+}
+
+/// Write a response to an IPC client.
+fn send_response(stream: &mut UnixStream, response: &IpcResponse) -> Result<(), Box<dyn Error>> {
+    let mut json = serde_json::to_string(response)?;
+    json.push('\n');
+    stream.write_all(json.as_bytes())?;
+    Ok(())
+}
+
+/// IPC clients subscribed to compositor events.
+#[derive(Debug, Default)]
+pub struct Subscribers {
+    streams: Vec<UnixStream>,
+}
+
+impl Subscribers {
+    /// Send an event to all subscribers.
+    ///
+    /// Subscribers which cannot keep up with the events are disconnected.
+    pub fn broadcast(&mut self, event: &IpcEvent) {
+        if self.streams.is_empty() {
+            return;
+        }
+
+        let mut json = match serde_json::to_string(event) {
+            Ok(json) => json,
+            Err(err) => {
+                error!("IPC event error: {err}");
+                return;
+            },
+        };
+        json.push('\n');
+
+        self.streams.retain(|mut stream| stream.write_all(json.as_bytes()).is_ok());
+    }
+}
+
+impl<B: Backend> Catacomb<B> {
+    /// Process a single IPC message.
+    ///
+    /// Returns `None` if the response is sent to the client asynchronously.
+    fn handle_ipc_message(
+        &mut self,
+        message: IpcMessage,
+        stream: &UnixStream,
+    ) -> Option<IpcResponse> {
+        // Reject messages for windows which do not exist.
+        if let IpcMessage::Primary { index }
+        | IpcMessage::Secondary { index }
+        | IpcMessage::Close { index } = message
+        {
+            if index >= self.windows.window_count() {
+                return Some(IpcResponse::error(format!("no window with index {index}")));
+            }
+        }
+
+        match message {
+            IpcMessage::Overview => self.windows.on_gesture(&self.output, Gesture::Overview),
+            IpcMessage::Home => self.windows.on_gesture(&self.output, Gesture::Home),
+            IpcMessage::Primary { index } => self.windows.show_primary(&self.output, index),
+            IpcMessage::Secondary { index } => self.windows.show_secondary(&self.output, index),
+            IpcMessage::Close { index } => self.windows.close(index),
+            IpcMessage::Orientation { orientation } => self.handle_orientation(orientation.into()),
+            IpcMessage::Mirror { enabled } => self.set_mirroring(enabled),
+            IpcMessage::TouchDebug => self.toggle_touch_debug(),
+            IpcMessage::Spawn { program, args } => {
+                if let Err(err) = daemon::spawn(&program, &args) {
+                    return Some(IpcResponse::error(format!("unable to spawn {program:?}: {err}")));
+                }
+            },
+            IpcMessage::Screenshot { path } => {
+                if !path.is_absolute() {
+                    return Some(IpcResponse::error(format!("path {path:?} is not absolute")));
+                }
+
+                let mut client = match stream.try_clone() {
+                    Ok(client) => client,
+                    Err(err) => return Some(IpcResponse::error(format!("capture failed: {err}"))),
+                };
+
+                // Reply once the next frame was written to the file.
+                self.capture_frame(path, move |result| {
+                    let response = match result {
+                        Ok(()) => IpcResponse::Ok,
+                        Err(err) => IpcResponse::error(format!("capture failed: {err}")),
+                    };
+                    let _ = send_response(&mut client, &response);
+                });
+
+                return None;
+            },
+            IpcMessage::Record { path } => {
+                if let Err(err) = self.start_recording(&path) {
+                    return Some(IpcResponse::error(format!(
+                        "unable to record to {path:?}: {err}"
+                    )));
+                }
+            },
+            IpcMessage::StopRecording => self.stop_recording(),
+            IpcMessage::Replay { path } => {
+                if let Err(err) = self.replay_input(&path) {
+                    return Some(IpcResponse::error(format!("unable to replay {path:?}: {err}")));
+                }
+            },
+            IpcMessage::Mode { output, mode } => {
+                if let Err(err) = self.backend.check_mode(&output, mode) {
+                    return Some(IpcResponse::error(err));
+                }
+
+                // Overrides are stored even for disconnected outputs.
+                match mode {
+                    Some(mode) => self.mode_overrides.insert(output, mode),
+                    None => self.mode_overrides.remove(&output),
+                };
+                self.backend.reload_modes();
+            },
+            IpcMessage::Subscribe => match stream.try_clone() {
+                Ok(subscriber) => self.ipc_subscribers.streams.push(subscriber),
+                Err(err) => return Some(IpcResponse::error(format!("unable to subscribe: {err}"))),
+            },
+        }
+
+        Some(IpcResponse::Ok)
+    }
+}
+
+impl From<Orientation> for IpcOrientation {
+    fn from(orientation: Orientation) -> Self {
+        match orientation {
+            Orientation::Portrait => IpcOrientation::Portrait,
+            Orientation::InversePortrait => IpcOrientation::InversePortrait,
+            Orientation::Landscape => IpcOrientation::Landscape,
+            Orientation::InverseLandscape => IpcOrientation::InverseLandscape,
+        }
+    }
+}
+
+impl From<IpcOrientation> for Orientation {
+    fn from(orientation: IpcOrientation) -> Self {
+        match orientation {
+            IpcOrientation::Portrait => Orientation::Portrait,
+            IpcOrientation::InversePortrait => Orientation::InversePortrait,
+            IpcOrientation::Landscape => Orientation::Landscape,
+            IpcOrientation::InverseLandscape => Orientation::InverseLandscape,
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Logging.
+//!
+//! All messages are written to stderr and optionally to a size-limited log
+//! file. Log levels can be overridden for individual modules through the
+//! `CATACOMB_LOG` environment variable, using comma-separated `[module=]level`
+//! directives:
+//!
+//! ```text
+//! CATACOMB_LOG=catacomb::input=trace,smithay=warn
+//! ```
+
+use std::cmp::Reverse;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use log::{LevelFilter, Log, Metadata, Record};
+use slog::Drain;
+
+/// Environment variable for per-module log filters.
+const FILTER_ENV: &str = "CATACOMB_LOG";
+
+/// Maximum log file size in bytes before it is rotated.
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Number of rotated log files which are kept around.
+const MAX_ROTATED_FILES: usize = 3;
+
+/// Install the global logger.
+pub fn init(level: LevelFilter, path: Option<PathBuf>) {
+    let mut filter = Filter::new(level);
+    let directives = env::var(FILTER_ENV).unwrap_or_default();
+    let invalid_directives = filter.parse(&directives);
+
+    let (file, file_error) = match path.map(LogFile::open) {
+        Some(Ok(file)) => (Some(Mutex::new(file)), None),
+        Some(Err(err)) => (None, Some(err)),
+        None => (None, None),
+    };
+
+    let max_level = filter.max_level();
+    let logger = Logger { filter, file, start: Instant::now() };
+    if log::set_boxed_logger(Box::new(logger)).is_err() {
+        return;
+    }
+    log::set_max_level(max_level);
+
+    for directive in invalid_directives {
+        log::warn!("Ignoring invalid {FILTER_ENV} directive {directive:?}");
+    }
+
+    if let Some(err) = file_error {
+        log::error!("Unable to open log file: {err}");
+    }
+}
+
+/// Smithay logger, forwarding all messages to the global logger.
+pub fn smithay() -> slog::Logger {
+    slog::Logger::root(slog_stdlog::StdLog.fuse(), slog::o!())
+}
+
+/// Logger writing to stderr and the log file.
+struct Logger {
+    filter: Filter,
+    file: Option<Mutex<LogFile>>,
+    start: Instant,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let elapsed = self.start.elapsed();
+        let line = format!(
+            "[{:>4}.{:06}] {:<5} {}: {}\n",
+            elapsed.as_secs(),
+            elapsed.subsec_micros(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        let _ = io::stderr().write_all(line.as_bytes());
+
+        if let Some(mut file) = self.file.as_ref().and_then(|file| file.lock().ok()) {
+            file.write(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+
+        if let Some(mut file) = self.file.as_ref().and_then(|file| file.lock().ok()) {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// Per-module log level filter.
+#[derive(Debug, PartialEq)]
+struct Filter {
+    default: LevelFilter,
+    /// Module filters, ordered from most to least specific.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    fn new(default: LevelFilter) -> Self {
+        Self { default, modules: Vec::new() }
+    }
+
+    /// Apply comma-separated `[module=]level` directives.
+    ///
+    /// Returns all directives which could not be parsed.
+    fn parse<'a>(&mut self, directives: &'a str) -> Vec<&'a str> {
+        let mut invalid = Vec::new();
+
+        for directive in directives.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (module, level) = match directive.split_once('=') {
+                Some((module, level)) => (Some(module.trim()), level.trim()),
+                None => (None, directive),
+            };
+
+            match (module, level.parse()) {
+                (Some(module), Ok(level)) if !module.is_empty() => {
+                    self.modules.retain(|(known, _)| known != module);
+                    self.modules.push((module.into(), level));
+                },
+                (None, Ok(level)) => self.default = level,
+                _ => invalid.push(directive),
+            }
+        }
+
+        self.modules.sort_by_key(|(module, _)| Reverse(module.len()));
+
+        invalid
+    }
+
+    /// Log level for a log target.
+    fn level(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| match target.strip_prefix(module.as_str()) {
+                Some(rest) => rest.is_empty() || rest.starts_with("::"),
+                None => false,
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// Most verbose level enabled for any module.
+    fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|(_, level)| *level).fold(self.default, Ord::max)
+    }
+}
+
+/// Size-limited log file.
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size })
+    }
+
+    /// Append a line, rotating the file once it grows too big.
+    fn write(&mut self, line: &[u8]) {
+        if self.size > 0 && self.size + line.len() as u64 > MAX_FILE_SIZE {
+            // The logger itself cannot be used while it is writing.
+            if let Err(err) = self.rotate() {
+                eprintln!("Unable to rotate log file: {err}");
+            }
+        }
+
+        if self.file.write_all(line).is_ok() {
+            self.size += line.len() as u64;
+        }
+    }
+
+    /// Move the current file to `<path>.1` and start a new one.
+    fn rotate(&mut self) -> io::Result<()> {
+        for index in (1..MAX_ROTATED_FILES).rev() {
+            let _ =
+                fs::rename(rotated_path(&self.path, index), rotated_path(&self.path, index + 1));
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+/// Path of a rotated log file.
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut rotated = OsString::from(path);
+    rotated.push(format!(".{index}"));
+    PathBuf::from(rotated)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn module_filters() {
+        let mut filter = Filter::new(LevelFilter::Info);
+        let invalid = filter.parse("catacomb::input=trace, smithay=warn,catacomb=debug");
+        assert!(invalid.is_empty());
+
+        assert_eq!(filter.level("catacomb::input"), LevelFilter::Trace);
+        assert_eq!(filter.level("catacomb::input::touch"), LevelFilter::Trace);
+        assert_eq!(filter.level("catacomb::inputs"), LevelFilter::Debug);
+        assert_eq!(filter.level("catacomb"), LevelFilter::Debug);
+        assert_eq!(filter.level("smithay::backend::drm"), LevelFilter::Warn);
+        assert_eq!(filter.level("calloop"), LevelFilter::Info);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn default_override() {
+        let mut filter = Filter::new(LevelFilter::Info);
+        let invalid = filter.parse("error,smithay=loud,=debug,catacomb");
+
+        assert_eq!(invalid, vec!["smithay=loud", "=debug", "catacomb"]);
+        assert_eq!(filter, Filter::new(LevelFilter::Error));
+    }
+
+    #[test]
+    fn rotated_file_name() {
+        let path = Path::new("/tmp/catacomb.log");
+        assert_eq!(rotated_path(path, 2), PathBuf::from("/tmp/catacomb.log.2"));
+    }
+}
@@ -1,28 +1,120 @@
 use std::env;
+use std::path::PathBuf;
 
+use clap::{ArgEnum, Parser};
+use log::{error, LevelFilter};
+
+use crate::catacomb::Startup;
+use crate::config::Config;
+
+mod capture;
 mod catacomb;
+mod clock;
 mod config;
+mod cursor;
+mod daemon;
 mod drawing;
+mod evdev;
 mod geometry;
+mod headless;
 mod input;
+mod ipc_server;
 mod layer;
+mod logging;
 mod orientation;
 mod output;
 mod overview;
+mod recording;
 mod shell;
+#[cfg(test)]
+mod test_support;
 mod udev;
 mod window;
 #[cfg(feature = "winit")]
 mod winit;
 
+/// Wayland mobile compositor.
+#[derive(Parser, Debug)]
+#[clap(author, version)]
+struct Options {
+    /// Rendering and input backend.
+    ///
+    /// By default winit is used when running inside another graphical
+    /// session, and udev otherwise.
+    #[clap(long, arg_enum)]
+    backend: Option<BackendKind>,
+
+    /// Wayland socket name.
+    #[clap(long)]
+    socket: Option<String>,
+
+    /// Configuration file path.
+    #[clap(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Maximum log level.
+    ///
+    /// Individual modules can be configured through the `CATACOMB_LOG`
+    /// environment variable, using `module=level` directives.
+    #[clap(long, default_value = "info")]
+    log_level: LevelFilter,
+
+    /// Additionally write logs to this file.
+    #[clap(long, parse(from_os_str))]
+    log_file: Option<PathBuf>,
+
+    /// Emulate touch input using the left mouse button.
+    ///
+    /// This is only supported by the winit backend.
+    #[clap(long)]
+    touch_emulation: bool,
+
+    /// Command started once the Wayland socket is ready.
+    #[clap(last = true)]
+    command: Vec<String>,
+}
+
+/// Available backends.
+#[derive(ArgEnum, Copy, Clone, Debug)]
+enum BackendKind {
+    Udev,
+    Winit,
+    Headless,
+}
+
+impl BackendKind {
+    /// Pick a backend based on the environment.
+    fn detect() -> Self {
+        if env::var_os("DISPLAY").is_none() && env::var_os("WAYLAND_DISPLAY").is_none() {
+            BackendKind::Udev
+        } else {
+            BackendKind::Winit
+        }
+    }
+}
+
 fn main() {
+    let options = Options::parse();
+
+    logging::init(options.log_level, options.log_file);
+
     // Do not turn children into zombies.
     unsafe { libc::signal(libc::SIGCHLD, libc::SIG_IGN) };
 
-    if env::var_os("DISPLAY").is_none() && env::var_os("WAYLAND_DISPLAY").is_none() {
-        udev::run();
-    } else {
+    let config = Config::load(options.config.or_else(config::default_path));
+    let startup = Startup { socket: options.socket, command: options.command };
+
+    let result = match options.backend.unwrap_or_else(BackendKind::detect) {
+        BackendKind::Udev => udev::run(config, startup),
         #[cfg(feature = "winit")]
-        winit::run();
+        BackendKind::Winit => winit::run(config, startup, options.touch_emulation),
+        #[cfg(not(feature = "winit"))]
+        BackendKind::Winit => Err("Catacomb was built without winit support".into()),
+        BackendKind::Headless => headless::run(config, startup),
+    };
+
+    if let Err(err) = result {
+        error!("{err}");
+        std::process::exit(1);
     }
 }
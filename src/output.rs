@@ -8,11 +8,9 @@ use smithay::utils::{Logical, Physical, Rectangle, Size, Transform};
 use smithay::wayland::output::{Mode, Output as SmithayOutput, PhysicalProperties};
 use smithay::wayland::shell::wlr_layer::{Anchor, ExclusiveZone};
 
+use crate::logging;
 use crate::orientation::Orientation;
 
-/// Use a fixed output scale.
-const SCALE: f64 = 2.;
-
 /// Wayland output, typically a screen.
 pub struct Output {
     /// Layer shell reserved space.
@@ -40,9 +38,10 @@ impl Output {
         name: impl Into<String>,
         mode: Mode,
         properties: PhysicalProperties,
+        scale: f64,
     ) -> Self {
-        let (output, global) = SmithayOutput::new(display, name.into(), properties, None);
-        let scale = SCALE;
+        let (output, global) =
+            SmithayOutput::new(display, name.into(), properties, logging::smithay());
 
         let mut output = Self {
             global: Some(global),
@@ -61,21 +60,26 @@ impl Output {
     }
 
     /// Create a new dummy output.
-    pub fn new_dummy(display: &mut Display) -> Self {
+    pub fn new_dummy(display: &mut Display, scale: f64) -> Self {
         let mode = Mode { size: (0, 0).into(), refresh: 0 };
-        Output::new(display, "dummy-0", mode, PhysicalProperties {
+        let properties = PhysicalProperties {
             subpixel: Subpixel::Unknown,
             model: "dummy-0".into(),
             make: "dummy-0".into(),
             size: (0, 0).into(),
-        })
+        };
+        Output::new(display, "dummy-0", mode, properties, scale)
     }
 
     /// Update the output's active mode.
+    ///
+    /// Since `wl_output` only supports integer scales, fractional scales are
+    /// rounded up for clients to render at sufficient resolution.
     #[inline]
     pub fn set_mode(&mut self, mode: Mode) {
         let transform = Some(self.transform.into());
-        self.output.change_current_state(Some(mode), transform, Some(self.scale as i32), None);
+        let scale = Some(self.scale.ceil() as i32);
+        self.output.change_current_state(Some(mode), transform, scale, None);
         self.output.set_preferred(mode);
         self.mode = mode;
     }
@@ -166,10 +170,32 @@ impl Output {
         self.orientation
     }
 
+    /// Stop advertising the output to clients.
+    ///
+    /// This is used once the output's display was disconnected.
+    pub fn deactivate(&mut self) {
+        if let Some(global) = self.global.take() {
+            global.destroy();
+        }
+    }
+
+    /// Check if the output is still advertised to clients.
+    pub fn active(&self) -> bool {
+        self.global.is_some()
+    }
+
     /// Output scale.
     pub fn scale(&self) -> f64 {
         self.scale
     }
+
+    /// Update the output scale.
+    pub fn set_scale(&mut self, scale: f64) {
+        self.scale = scale;
+
+        // Update output mode to apply scale.
+        self.set_mode(self.mode);
+    }
 }
 
 impl Deref for Output {
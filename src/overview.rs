@@ -8,26 +8,13 @@ use std::time::Instant;
 use smithay::backend::renderer::gles2::{ffi, Gles2Frame, Gles2Renderer};
 use smithay::utils::{Logical, Point, Rectangle, Size};
 
+use crate::clock;
+use crate::config::{Config, OverviewConfig};
 use crate::drawing::Graphics;
 use crate::geometry::Vector;
 use crate::output::Output;
 use crate::window::Window;
 
-/// Percentage of output width reserved for the main window in the application
-/// overview.
-pub const FG_OVERVIEW_PERCENTAGE: f64 = 0.75;
-
-/// Percentage of remaining space reserved for background windows in the
-/// application overview.
-const BG_OVERVIEW_PERCENTAGE: f64 = 0.5;
-
-/// Percentage of the screen for the drop highlight areas.
-const DRAG_AND_DROP_PERCENTAGE: f64 = 0.3;
-
-/// Percentage of the output height a window can be moved before closing it in
-/// the overview.
-const OVERVIEW_CLOSE_DISTANCE: f64 = 0.25;
-
 /// Animation speed for the return from close, lower means faster.
 const CLOSE_CANCEL_ANIMATION_SPEED: f64 = 0.3;
 
@@ -59,12 +46,17 @@ impl Overview {
     }
 
     /// Focused window bounds.
-    pub fn focused_bounds(&self, output: &Output, window_count: usize) -> Rectangle<i32, Logical> {
+    pub fn focused_bounds(
+        &self,
+        output: &Output,
+        config: &OverviewConfig,
+        window_count: usize,
+    ) -> Rectangle<i32, Logical> {
         let available = output.available();
-        let window_size = available.size.scale(FG_OVERVIEW_PERCENTAGE);
+        let window_size = available.size.scale(config.foreground_percentage);
         let x = overview_x_position(
-            FG_OVERVIEW_PERCENTAGE,
-            BG_OVERVIEW_PERCENTAGE,
+            config.foreground_percentage,
+            config.background_percentage,
             available.size.w,
             window_size.w,
             self.focused_index(window_count) as f64 + self.x_offset,
@@ -89,7 +81,7 @@ impl Overview {
         // Handle bounce-back from overdrag/cancelled application close.
 
         // Compute framerate-independent delta.
-        let delta = last_overdrag_step.elapsed().as_millis() as f64;
+        let delta = clock::elapsed(*last_overdrag_step).as_millis() as f64;
         let overdrag_delta = delta / OVERDRAG_ANIMATION_SPEED;
         let close_delta = delta / CLOSE_CANCEL_ANIMATION_SPEED;
 
@@ -103,7 +95,7 @@ impl Overview {
         // Close window bounce-back.
         self.y_offset -= close_delta.min(self.y_offset.abs()).copysign(self.y_offset);
 
-        *last_overdrag_step = Instant::now();
+        *last_overdrag_step = clock::now();
     }
 
     /// Render the overview.
@@ -112,9 +104,11 @@ impl Overview {
         renderer: &mut Gles2Renderer,
         frame: &mut Gles2Frame,
         output: &Output,
+        config: &Config,
         windows: &[Rc<RefCell<Window>>],
         graphics: &mut Graphics,
     ) {
+        let overview_config = &config.overview;
         let window_count = windows.len() as i32;
         self.clamp_offset(window_count);
 
@@ -131,10 +125,10 @@ impl Overview {
 
         // Maximum window size. Bigger windows will be truncated.
         let available = output.available();
-        let max_size = available.size.scale(FG_OVERVIEW_PERCENTAGE);
+        let max_size = available.size.scale(overview_config.foreground_percentage);
 
         // Window decoration.
-        let decoration = graphics.decoration(renderer, output);
+        let decoration = graphics.decoration(renderer, output, config);
         let border_width = Graphics::border_width(output);
         let title_height = Graphics::title_height(output);
 
@@ -145,8 +139,8 @@ impl Overview {
             // Window boundaries.
             let mut bounds = Rectangle::from_loc_and_size(available.loc, max_size);
             bounds.loc.x += overview_x_position(
-                FG_OVERVIEW_PERCENTAGE,
-                BG_OVERVIEW_PERCENTAGE,
+                overview_config.foreground_percentage,
+                overview_config.background_percentage,
                 available.size.w,
                 max_size.w,
                 position as f64 - self.x_offset.fract().round() + self.x_offset.fract(),
@@ -165,13 +159,20 @@ impl Overview {
             );
             decoration.draw_at(frame, output, decoration_bounds, 1., None);
 
-            window.draw(renderer, frame, output, FG_OVERVIEW_PERCENTAGE, bounds, None);
+            window.draw(
+                renderer,
+                frame,
+                output,
+                overview_config.foreground_percentage,
+                bounds,
+                None,
+            );
         }
     }
 
     /// Check if the active window has exceeded the minimum close distance.
-    pub fn should_close(&self, output: &Output) -> bool {
-        let close_distance = output.available().size.h as f64 * OVERVIEW_CLOSE_DISTANCE;
+    pub fn should_close(&self, output: &Output, config: &OverviewConfig) -> bool {
+        let close_distance = output.available().size.h as f64 * config.close_distance;
         self.y_offset.abs() >= close_distance
     }
 
@@ -217,19 +218,21 @@ impl DragAndDrop {
         renderer: &mut Gles2Renderer,
         frame: &mut Gles2Frame,
         output: &Output,
+        config: &Config,
         windows: &[Rc<RefCell<Window>>],
         graphics: &mut Graphics,
     ) {
+        let overview_config = &config.overview;
         let available = output.available();
         let border_width = Graphics::border_width(output);
         let title_height = Graphics::title_height(output);
 
         // Calculate window bounds.
-        let max_size = available.size.scale(FG_OVERVIEW_PERCENTAGE);
+        let max_size = available.size.scale(overview_config.foreground_percentage);
         let mut bounds = Rectangle::from_loc_and_size(available.loc, max_size);
         bounds.loc.x += overview_x_position(
-            FG_OVERVIEW_PERCENTAGE,
-            BG_OVERVIEW_PERCENTAGE,
+            overview_config.foreground_percentage,
+            overview_config.background_percentage,
             available.size.w,
             max_size.w,
             self.overview_x_offset.fract() - self.overview_x_offset.fract().round(),
@@ -240,7 +243,7 @@ impl DragAndDrop {
         bounds.loc += self.window_position.to_i32_round();
 
         // Render decoration for the window.
-        let decoration = graphics.decoration(renderer, output);
+        let decoration = graphics.decoration(renderer, output, config);
         let decoration_bounds = Rectangle::from_loc_and_size(
             (bounds.loc.x - border_width, bounds.loc.y - title_height),
             decoration.size(),
@@ -249,7 +252,7 @@ impl DragAndDrop {
 
         // Render the window being drag-and-dropped.
         let mut window = windows[self.window_index].borrow_mut();
-        window.draw(renderer, frame, output, FG_OVERVIEW_PERCENTAGE, bounds, None);
+        window.draw(renderer, frame, output, overview_config.foreground_percentage, bounds, None);
 
         // Set custom OpenGL blending function.
         let _ = renderer.with_context(|_, gl| unsafe {
@@ -257,15 +260,17 @@ impl DragAndDrop {
         });
 
         // Get bounds of the drop areas.
-        let (primary_bounds, secondary_bounds) = self.drop_bounds(output);
+        let (primary_bounds, secondary_bounds) = self.drop_bounds(output, overview_config);
 
         // Render the drop areas.
         let scale = cmp::max(available.size.w, available.size.h) as f64;
         for bounds in [primary_bounds, secondary_bounds] {
             if bounds.to_f64().contains(self.touch_position) {
-                graphics.active_drop_target(renderer).draw_at(frame, output, bounds, scale, None);
+                let texture = graphics.active_drop_target(renderer, &config.colors);
+                texture.draw_at(frame, output, bounds, scale, None);
             } else {
-                graphics.drop_target(renderer).draw_at(frame, output, bounds, scale, None);
+                let texture = graphics.drop_target(renderer, &config.colors);
+                texture.draw_at(frame, output, bounds, scale, None);
             }
         }
 
@@ -279,10 +284,12 @@ impl DragAndDrop {
     pub fn drop_bounds(
         &self,
         output: &Output,
+        config: &OverviewConfig,
     ) -> (Rectangle<i32, Logical>, Rectangle<i32, Logical>) {
         let available = output.available();
+        let percentage = config.drag_and_drop_percentage;
         if available.size.h > available.size.w {
-            let dnd_height = (available.size.h as f64 * DRAG_AND_DROP_PERCENTAGE).round() as i32;
+            let dnd_height = (available.size.h as f64 * percentage).round() as i32;
             let size = Size::from((available.size.w, dnd_height));
             let primary = Rectangle::from_loc_and_size(available.loc, size);
 
@@ -291,7 +298,7 @@ impl DragAndDrop {
 
             (primary, secondary)
         } else {
-            let dnd_width = (available.size.w as f64 * DRAG_AND_DROP_PERCENTAGE).round() as i32;
+            let dnd_width = (available.size.w as f64 * percentage).round() as i32;
             let size = Size::from((dnd_width, available.size.h));
             let primary = Rectangle::from_loc_and_size(available.loc, size);
 
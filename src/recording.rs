@@ -0,0 +1,287 @@
+//! Input recording and replay.
+//!
+//! Recordings are stored as JSON lines, each containing a single input event
+//! and the time it was received relative to the start of the recording.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use calloop::timer::{Timer, TimerHandle};
+use calloop::LoopHandle;
+use log::error;
+use serde::{Deserialize, Serialize};
+use smithay::backend::input::{ButtonState, KeyState, TouchSlot};
+
+use crate::catacomb::{Backend, Catacomb};
+use crate::clock;
+use crate::input::{HardwareSwitch, ScrollEvent, TouchEvent, TouchEventType};
+
+/// Recorded input event.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputRecord {
+    TouchDown { slot: u32, time: u32, x: f64, y: f64 },
+    TouchMotion { slot: u32, time: u32, x: f64, y: f64 },
+    TouchUp { slot: u32, time: u32 },
+    TouchFrame,
+    TouchCancel { slot: u32 },
+    Key { keycode: u32, pressed: bool, time: u32 },
+    PointerMotion { time: u32, x: f64, y: f64 },
+    PointerButton { button: u32, pressed: bool, time: u32 },
+    PointerAxis(ScrollEvent),
+    TouchpadSwipeBegin { fingers: u32 },
+    TouchpadSwipeUpdate { dx: f64, dy: f64 },
+    TouchpadPinchBegin { fingers: u32 },
+    TouchpadPinchUpdate { scale: f64 },
+    TouchpadGestureEnd { cancelled: bool },
+    Switch { switch: HardwareSwitch, on: bool },
+}
+
+/// Single line of a recording.
+#[derive(Serialize, Deserialize, Debug)]
+struct RecordLine {
+    /// Microseconds since the start of the recording.
+    elapsed: u64,
+    event: InputRecord,
+}
+
+/// Input event recorder.
+pub struct Recorder {
+    file: BufWriter<File>,
+    slots: Vec<TouchSlot>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(Self { file, start: clock::now(), slots: Default::default() })
+    }
+
+    /// Append an event to the recording.
+    pub fn record(&mut self, event: InputRecord) -> Result<(), Box<dyn Error>> {
+        let elapsed = clock::elapsed(self.start).as_micros() as u64;
+        serde_json::to_writer(&mut self.file, &RecordLine { elapsed, event })?;
+        self.file.write_all(b"\n")?;
+
+        // Flush every event, so recordings survive compositor crashes.
+        self.file.flush()?;
+
+        Ok(())
+    }
+
+    /// Stable ID for a touch slot.
+    pub fn slot_id(&mut self, slot: TouchSlot) -> u32 {
+        match self.slots.iter().position(|known| *known == slot) {
+            Some(index) => index as u32,
+            None => {
+                self.slots.push(slot);
+                self.slots.len() as u32 - 1
+            },
+        }
+    }
+
+    /// Convert a touch event to its recorded representation.
+    pub fn touch_record(&mut self, event: &TouchEvent) -> InputRecord {
+        let slot = self.slot_id(event.slot);
+        let (x, y) = (event.position.x, event.position.y);
+        match event.ty {
+            TouchEventType::Down => InputRecord::TouchDown { slot, time: event.time, x, y },
+            TouchEventType::Motion => InputRecord::TouchMotion { slot, time: event.time, x, y },
+            TouchEventType::Up => InputRecord::TouchUp { slot, time: event.time },
+        }
+    }
+}
+
+/// Playback state for input recordings.
+pub struct InputReplay {
+    events: VecDeque<(Duration, InputRecord)>,
+    timer: TimerHandle<()>,
+    start: Instant,
+}
+
+impl InputReplay {
+    pub fn new<B: Backend>(loop_handle: LoopHandle<'_, Catacomb<B>>) -> Self {
+        let timer = Timer::new().expect("create replay timer");
+        let timer_handle = timer.handle();
+        loop_handle
+            .insert_source(timer, |_, _, catacomb| catacomb.on_replay_tick())
+            .expect("insert replay timer");
+
+        Self { timer: timer_handle, start: clock::now(), events: Default::default() }
+    }
+
+    /// Replace the active replay with a new recording.
+    fn start(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let events = load(path)?;
+
+        self.timer.cancel_all_timeouts();
+        self.timer.add_timeout(Duration::ZERO, ());
+        self.start = clock::now();
+        self.events = events;
+
+        Ok(())
+    }
+}
+
+/// Read all events of a recording, with their offset from its start.
+fn load(path: &Path) -> Result<VecDeque<(Duration, InputRecord)>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+
+    let mut events = VecDeque::new();
+    for (i, line) in content.lines().enumerate().filter(|(_, line)| !line.is_empty()) {
+        let line: RecordLine =
+            serde_json::from_str(line).map_err(|err| format!("line {}: {err}", i + 1))?;
+        events.push_back((Duration::from_micros(line.elapsed), line.event));
+    }
+
+    Ok(events)
+}
+
+impl<B: Backend> Catacomb<B> {
+    /// Start recording all input events to a file.
+    pub fn start_recording(&mut self, path: &Path) -> io::Result<()> {
+        self.input_recorder = Some(Recorder::new(path)?);
+        Ok(())
+    }
+
+    /// Stop the active input recording.
+    pub fn stop_recording(&mut self) {
+        self.input_recorder = None;
+    }
+
+    /// Record an input event, if recording is active.
+    pub fn record_input<F: FnOnce(&mut Recorder) -> InputRecord>(&mut self, event_fun: F) {
+        let recorder = match &mut self.input_recorder {
+            Some(recorder) => recorder,
+            None => return,
+        };
+
+        let event = event_fun(recorder);
+        if let Err(err) = recorder.record(event) {
+            error!("Recording error: {err}; recording stopped");
+            self.input_recorder = None;
+        }
+    }
+
+    /// Feed a recording back through input handling.
+    pub fn replay_input(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.input_replay.start(path)
+    }
+
+    /// Apply all replayed events which are due.
+    fn on_replay_tick(&mut self) {
+        let elapsed = clock::elapsed(self.input_replay.start);
+        while let Some(&(offset, event)) = self.input_replay.events.front() {
+            if offset > elapsed {
+                self.input_replay.timer.add_timeout(offset - elapsed, ());
+                break;
+            }
+
+            self.input_replay.events.pop_front();
+            self.apply_input_record(event);
+        }
+    }
+
+    /// Process a single recorded input event.
+    fn apply_input_record(&mut self, event: InputRecord) {
+        let touch_event = |ty, slot: u32, time, x: f64, y: f64| {
+            TouchEvent::new(ty, TouchSlot::from(Some(slot)), time, (x, y).into())
+        };
+
+        match event {
+            InputRecord::TouchDown { slot, time, x, y } => {
+                self.queue_touch_event(touch_event(TouchEventType::Down, slot, time, x, y));
+            },
+            InputRecord::TouchMotion { slot, time, x, y } => {
+                self.queue_touch_event(touch_event(TouchEventType::Motion, slot, time, x, y));
+            },
+            InputRecord::TouchUp { slot, time } => {
+                let (x, y) = self.touch_state.position.into();
+                self.queue_touch_event(touch_event(TouchEventType::Up, slot, time, x, y));
+            },
+            InputRecord::TouchFrame => self.on_touch_frame(),
+            InputRecord::TouchCancel { slot } => self.on_touch_cancel(TouchSlot::from(Some(slot))),
+            InputRecord::Key { keycode, pressed, time } => {
+                let state = if pressed { KeyState::Pressed } else { KeyState::Released };
+                self.on_key(keycode, state, time);
+            },
+            InputRecord::PointerMotion { time, x, y } => self.on_pointer_move((x, y).into(), time),
+            InputRecord::PointerButton { button, pressed, time } => {
+                let state = if pressed { ButtonState::Pressed } else { ButtonState::Released };
+                self.on_pointer_button(button, state, time);
+            },
+            InputRecord::PointerAxis(event) => self.on_scroll(event),
+            InputRecord::TouchpadSwipeBegin { fingers } => self.on_touchpad_swipe_begin(fingers),
+            InputRecord::TouchpadSwipeUpdate { dx, dy } => {
+                self.on_touchpad_swipe_update((dx, dy).into());
+            },
+            InputRecord::TouchpadPinchBegin { fingers } => self.on_touchpad_pinch_begin(fingers),
+            InputRecord::TouchpadPinchUpdate { scale } => self.on_touchpad_pinch_update(scale),
+            InputRecord::TouchpadGestureEnd { cancelled } => {
+                self.on_touchpad_gesture_end(cancelled);
+            },
+            InputRecord::Switch { switch, on } => self.on_switch(switch, on),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, process};
+
+    use super::*;
+    use crate::test_support::TestCompositor;
+
+    /// Read the events of a recording, without their timing.
+    fn recorded_events(path: &Path) -> Vec<InputRecord> {
+        load(path).unwrap().into_iter().map(|(_, event)| event).collect()
+    }
+
+    #[test]
+    fn record_replay_roundtrip() {
+        let recording = env::temp_dir().join(format!("catacomb-recording-{}", process::id()));
+        let replay = env::temp_dir().join(format!("catacomb-replay-{}", process::id()));
+
+        let mut test = TestCompositor::new();
+        test.catacomb.start_recording(&recording).unwrap();
+        test.swipe((100., 100.), (300., 400.));
+        test.advance_time(Duration::from_millis(50));
+        test.catacomb.on_key(30, KeyState::Pressed, 50);
+        test.catacomb.on_key(30, KeyState::Released, 60);
+        test.catacomb.on_pointer_move((50., 60.).into(), 70);
+        test.catacomb.on_pointer_button(0x110, ButtonState::Pressed, 80);
+        test.catacomb.on_touchpad_swipe_begin(3);
+        test.catacomb.on_touchpad_swipe_update((10., 0.).into());
+        test.catacomb.on_touchpad_gesture_end(true);
+        test.catacomb.on_switch(HardwareSwitch::TabletMode, true);
+        test.catacomb.stop_recording();
+        test.roundtrip();
+
+        let events = recorded_events(&recording);
+        assert_eq!(events.len(), 14);
+        assert_eq!(events[0], InputRecord::TouchDown { slot: 0, time: 0, x: 100., y: 100. });
+        assert_eq!(events[6], InputRecord::Key { keycode: 30, pressed: true, time: 50 });
+        assert_eq!(events[8], InputRecord::PointerMotion { time: 70, x: 50., y: 60. });
+        assert_eq!(events[13], InputRecord::Switch {
+            switch: HardwareSwitch::TabletMode,
+            on: true
+        });
+
+        // Replaying the recording must produce the same events again.
+        test.catacomb.start_recording(&replay).unwrap();
+        test.catacomb.replay_input(&recording).unwrap();
+        test.advance_time(Duration::from_secs(1));
+        test.catacomb.on_replay_tick();
+        test.catacomb.stop_recording();
+
+        assert_eq!(recorded_events(&replay), events);
+
+        let _ = fs::remove_file(recording);
+        let _ = fs::remove_file(replay);
+    }
+}
@@ -1,5 +1,6 @@
 //! Wayland shells.
 
+use log::debug;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
 use smithay::reexports::wayland_server::{DispatchData, Display};
 use smithay::wayland::compositor;
@@ -7,11 +8,12 @@ use smithay::wayland::shell::wlr_layer::{self, LayerShellRequest};
 use smithay::wayland::shell::xdg::{self as xdg_shell, XdgRequest};
 
 use crate::catacomb::Catacomb;
+use crate::logging;
 
 /// Initialize all available shells.
 pub fn init<B: 'static>(display: &mut Display) {
     // Create the compositor and register a surface commit handler.
-    compositor::compositor_init(display, surface_commit::<B>, None);
+    compositor::compositor_init(display, surface_commit::<B>, logging::smithay());
 
     // XDG Shell.
     let _ = xdg_shell::xdg_shell_init(
@@ -19,7 +21,8 @@ pub fn init<B: 'static>(display: &mut Display) {
         |event, mut data| match event {
             XdgRequest::NewToplevel { surface } => {
                 let catacomb = data.get::<Catacomb<B>>().unwrap();
-                catacomb.windows.add(surface, &catacomb.output);
+                let desktop = catacomb.external_outputs.first().map(|external| &external.output);
+                catacomb.windows.add(surface, &catacomb.output, desktop);
             },
             XdgRequest::AckConfigure { surface, .. } => {
                 // Request new frames after each resize.
@@ -35,9 +38,9 @@ pub fn init<B: 'static>(display: &mut Display) {
             },
             XdgRequest::Grab { .. } => (),
             XdgRequest::NewClient { .. } => (),
-            _ => eprintln!("UNHANDLED EVENT: {event:?}"),
+            _ => debug!("Unhandled XDG shell request: {event:?}"),
         },
-        None,
+        logging::smithay(),
     );
 
     // Layer shell.
@@ -50,7 +53,7 @@ pub fn init<B: 'static>(display: &mut Display) {
             },
             LayerShellRequest::AckConfigure { .. } => (),
         },
-        None,
+        logging::smithay(),
     );
 }
 
@@ -61,5 +64,9 @@ fn surface_commit<B: 'static>(surface: WlSurface, mut data: DispatchData) {
     }
 
     let catacomb = data.get::<Catacomb<B>>().unwrap();
+    if catacomb.cursor.surface_commit(&surface, &catacomb.output) {
+        return;
+    }
+
     catacomb.windows.surface_commit(&surface, &mut catacomb.output);
 }
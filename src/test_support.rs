@@ -0,0 +1,559 @@
+//! Integration test harness.
+//!
+//! This runs the compositor in-process with a fake backend and connects a
+//! scripted Wayland client to it through a private socket. Both sides are
+//! dispatched on the test thread, so every test is fully deterministic.
+
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
+use std::os::unix::net::UnixStream;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use smithay::backend::input::{ButtonState, TouchSlot};
+use smithay::backend::renderer::gles2::{Gles2Frame, Gles2Renderer};
+use smithay::reexports::calloop::EventLoop;
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface as ServerSurface;
+use smithay::reexports::wayland_server::Display as ServerDisplay;
+use smithay::utils::{Logical, Point};
+use smithay::wayland::output::Mode;
+use wayland_client::protocol::wl_compositor::WlCompositor;
+use wayland_client::protocol::wl_display::WlDisplay;
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::protocol::wl_shm::{Format, WlShm};
+use wayland_client::protocol::wl_surface::WlSurface;
+use wayland_client::protocol::{wl_pointer, wl_touch};
+use wayland_client::{Attached, Display, EventQueue, GlobalManager, Main};
+use wayland_protocols::wlr::unstable::layer_shell::v1::client::zwlr_layer_shell_v1::{
+    Layer, ZwlrLayerShellV1,
+};
+use wayland_protocols::wlr::unstable::layer_shell::v1::client::zwlr_layer_surface_v1::{
+    self, Anchor,
+};
+use wayland_protocols::xdg_shell::client::xdg_surface::{self, XdgSurface};
+use wayland_protocols::xdg_shell::client::xdg_wm_base::{self, XdgWmBase};
+use wayland_protocols::xdg_shell::client::{xdg_popup, xdg_toplevel};
+
+use crate::capture::Capture;
+use crate::catacomb::{Backend, Catacomb, Render};
+use crate::clock;
+use crate::config::Config;
+use crate::headless::HeadlessDevice;
+use crate::input::{TouchEvent, TouchEventType};
+
+/// Physical size of the test output.
+const OUTPUT_SIZE: (i32, i32) = (720, 1440);
+
+/// Maximum number of roundtrips before the compositor is considered stuck.
+const MAX_ROUNDTRIPS: usize = 100;
+
+/// Compositor and client running on the test thread.
+pub struct TestCompositor {
+    pub catacomb: Catacomb<TestBackend>,
+    event_loop: EventLoop<'static, Catacomb<TestBackend>>,
+    client: TestClient,
+    globals: Globals,
+    start: Instant,
+
+    /// Offscreen renderer for captures, created on demand.
+    renderer: Option<HeadlessDevice>,
+}
+
+impl TestCompositor {
+    /// Start the compositor and connect the test client.
+    pub fn new() -> Self {
+        let mut event_loop = EventLoop::try_new().expect("event loop");
+        let display = ServerDisplay::new();
+        let mut catacomb =
+            Catacomb::with_display(&mut event_loop, display, Config::default(), TestBackend);
+
+        // Use a fixed output size.
+        let mode = Mode { size: OUTPUT_SIZE.into(), refresh: 60_000 };
+        catacomb.output.set_mode(mode);
+
+        // Connect the client through a private socket.
+        let (server_socket, client_socket) = UnixStream::pair().expect("create socket pair");
+        client_socket.set_nonblocking(true).expect("nonblocking client socket");
+        let display = catacomb.display.clone();
+        unsafe { display.borrow_mut().create_client(server_socket.into_raw_fd(), &mut catacomb) };
+        let mut client = TestClient::new(client_socket);
+
+        // Wait for all globals to be advertised.
+        roundtrip(&mut catacomb, &mut event_loop, &mut client);
+        let globals = Globals::new(&client.globals);
+
+        Self { catacomb, event_loop, client, globals, start: clock::now(), renderer: None }
+    }
+
+    /// Dispatch both compositor and client until neither has work left.
+    pub fn roundtrip(&mut self) {
+        roundtrip(&mut self.catacomb, &mut self.event_loop, &mut self.client);
+    }
+
+    /// Render the current state offscreen and read back the frame.
+    ///
+    /// Returns `None` if no EGL renderer is available on the test machine.
+    pub fn capture(&mut self) -> Option<Capture> {
+        if self.renderer.is_none() {
+            self.renderer = HeadlessDevice::new(&self.catacomb).ok();
+        }
+        let mut device = self.renderer.as_mut()?;
+
+        let mut capture = None;
+        let result = device.render(&mut self.catacomb, |catacomb, renderer, frame, _| {
+            // Always redraw the entire output.
+            catacomb.draw(renderer, frame, 0);
+            capture = Some(Capture::read(renderer, catacomb.output.physical_resolution()));
+        });
+        result.expect("render capture");
+
+        Some(capture?.expect("read framebuffer"))
+    }
+
+    /// Move the compositor's clock forward.
+    pub fn advance_time(&mut self, duration: Duration) {
+        clock::advance(duration);
+    }
+
+    /// Create a new XDG toplevel.
+    pub fn create_toplevel(&mut self, app_id: &str) -> TestToplevel {
+        let surface = self.globals.compositor.create_surface();
+        let xdg_surface = self.globals.wm_base.get_xdg_surface(&surface);
+        let toplevel = xdg_surface.get_toplevel();
+        toplevel.set_app_id(app_id.into());
+
+        let state = Rc::new(RefCell::new(SurfaceState::default()));
+        let toplevel_state = state.clone();
+        toplevel.quick_assign(move |_, event, _| match event {
+            xdg_toplevel::Event::Configure { width, height, .. } => {
+                toplevel_state.borrow_mut().pending_size = (width, height);
+            },
+            xdg_toplevel::Event::Close => toplevel_state.borrow_mut().closed = true,
+            _ => (),
+        });
+        ack_xdg_configures(&xdg_surface, &surface, &state);
+
+        surface.commit();
+
+        TestToplevel { surface, xdg_surface, state }
+    }
+
+    /// Create a new XDG popup.
+    pub fn create_popup(&mut self, parent: &TestToplevel, size: (i32, i32)) -> TestPopup {
+        let positioner = self.globals.wm_base.create_positioner();
+        positioner.set_size(size.0, size.1);
+        positioner.set_anchor_rect(0, 0, 1, 1);
+
+        let surface = self.globals.compositor.create_surface();
+        let xdg_surface = self.globals.wm_base.get_xdg_surface(&surface);
+        let parent: &XdgSurface = &parent.xdg_surface;
+        let popup = xdg_surface.get_popup(Some(parent), &positioner);
+        positioner.destroy();
+
+        let state = Rc::new(RefCell::new(SurfaceState::default()));
+        let popup_state = state.clone();
+        popup.quick_assign(move |_, event, _| match event {
+            xdg_popup::Event::Configure { width, height, .. } => {
+                popup_state.borrow_mut().pending_size = (width, height);
+            },
+            xdg_popup::Event::PopupDone => popup_state.borrow_mut().closed = true,
+            _ => (),
+        });
+        ack_xdg_configures(&xdg_surface, &surface, &state);
+
+        surface.commit();
+
+        TestPopup { state }
+    }
+
+    /// Create a new layer shell surface.
+    pub fn create_layer(
+        &mut self,
+        layer: Layer,
+        anchor: Anchor,
+        size: (u32, u32),
+        exclusive_zone: i32,
+    ) -> TestLayer {
+        let surface = self.globals.compositor.create_surface();
+        let layer_surface =
+            self.globals.layer_shell.get_layer_surface(&surface, None, layer, "test".into());
+        layer_surface.set_size(size.0, size.1);
+        layer_surface.set_anchor(anchor);
+        layer_surface.set_exclusive_zone(exclusive_zone);
+
+        let state = Rc::new(RefCell::new(SurfaceState::default()));
+        let layer_state = state.clone();
+        let layer_wl_surface = surface.clone();
+        layer_surface.quick_assign(move |layer_surface, event, _| match event {
+            zwlr_layer_surface_v1::Event::Configure { serial, width, height } => {
+                let mut state = layer_state.borrow_mut();
+                if !state.ignore_configures {
+                    layer_surface.ack_configure(serial);
+                    state.size = Some((width as i32, height as i32));
+                    layer_wl_surface.commit();
+                }
+            },
+            zwlr_layer_surface_v1::Event::Closed => layer_state.borrow_mut().closed = true,
+            _ => (),
+        });
+
+        surface.commit();
+
+        TestLayer { state }
+    }
+
+    /// Attach a buffer matching the toplevel's last acknowledged configure.
+    pub fn attach_buffer(&mut self, toplevel: &TestToplevel) {
+        let (width, height) = toplevel.size().expect("configured toplevel");
+        let stride = width * 4;
+        let size = stride * height;
+
+        let file = unsafe {
+            let fd = libc::memfd_create(b"catacomb-test\0".as_ptr() as *const _, libc::MFD_CLOEXEC);
+            assert!(fd >= 0, "create shm file");
+            File::from_raw_fd(fd)
+        };
+        file.set_len(size as u64).expect("resize shm file");
+
+        let pool = self.globals.shm.create_pool(file.as_raw_fd(), size);
+        let buffer = pool.create_buffer(0, width, height, stride, Format::Argb8888);
+        pool.destroy();
+
+        toplevel.surface.attach(Some(&buffer), 0, 0);
+        toplevel.surface.damage(0, 0, width, height);
+        toplevel.surface.commit();
+
+        // Send the file descriptor before it is closed.
+        self.client.display.flush().expect("client flush");
+    }
+
+    /// Request a frame callback with the toplevel's next commit.
+    ///
+    /// Returns a flag which is set once the callback is done.
+    pub fn request_frame(&mut self, toplevel: &TestToplevel) -> Rc<Cell<bool>> {
+        let done = Rc::new(Cell::new(false));
+        let frame_done = done.clone();
+        toplevel.surface.frame().quick_assign(move |_, _, _| frame_done.set(true));
+        toplevel.surface.commit();
+
+        done
+    }
+
+    /// Bind the seat's pointer and track its events.
+    pub fn bind_pointer(&mut self) -> Rc<RefCell<PointerState>> {
+        let state = Rc::new(RefCell::new(PointerState::default()));
+        let pointer_state = state.clone();
+        self.globals.seat.get_pointer().quick_assign(move |_, event, _| {
+            let mut state = pointer_state.borrow_mut();
+            match event {
+                wl_pointer::Event::Enter { surface, .. } => {
+                    state.focus = Some(surface.as_ref().id())
+                },
+                wl_pointer::Event::Leave { .. } => state.focus = None,
+                wl_pointer::Event::Button { button, state: button_state, .. } => {
+                    state.buttons.push((button, button_state));
+                },
+                _ => (),
+            }
+        });
+        self.roundtrip();
+
+        state
+    }
+
+    /// Bind the seat's touch and record its events.
+    pub fn bind_touch(&mut self) -> Rc<RefCell<Vec<ClientTouchEvent>>> {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let touch_events = events.clone();
+        self.globals.seat.get_touch().quick_assign(move |_, event, _| {
+            let event = match event {
+                wl_touch::Event::Down { id, x, y, .. } => {
+                    ClientTouchEvent::Down { id, position: (x, y) }
+                },
+                wl_touch::Event::Motion { id, x, y, .. } => {
+                    ClientTouchEvent::Motion { id, position: (x, y) }
+                },
+                wl_touch::Event::Up { id, .. } => ClientTouchEvent::Up { id },
+                wl_touch::Event::Cancel => ClientTouchEvent::Cancel,
+                _ => return,
+            };
+            touch_events.borrow_mut().push(event);
+        });
+        self.roundtrip();
+
+        events
+    }
+
+    /// Move the pointer to a new position.
+    pub fn pointer_motion(&mut self, position: impl Into<Point<f64, Logical>>) {
+        let time = self.time();
+        self.catacomb.on_pointer_move(position.into(), time);
+    }
+
+    /// Press or release a pointer button.
+    pub fn pointer_button(&mut self, button: u32, state: ButtonState) {
+        let time = self.time();
+        self.catacomb.on_pointer_button(button, state, time);
+    }
+
+    /// Start a new touch point.
+    pub fn touch_down(&mut self, slot: u32, position: impl Into<Point<f64, Logical>>) {
+        self.queue_touch(TouchEventType::Down, slot, position.into());
+    }
+
+    /// Move an active touch point.
+    pub fn touch_motion(&mut self, slot: u32, position: impl Into<Point<f64, Logical>>) {
+        self.queue_touch(TouchEventType::Motion, slot, position.into());
+    }
+
+    /// Release an active touch point.
+    pub fn touch_up(&mut self, slot: u32) {
+        let position = self.catacomb.touch_state.position;
+        self.queue_touch(TouchEventType::Up, slot, position);
+    }
+
+    /// Cancel a touch point.
+    pub fn touch_cancel(&mut self, slot: u32) {
+        self.catacomb.on_touch_cancel(TouchSlot::from(Some(slot)));
+    }
+
+    /// Apply all queued touch events.
+    pub fn touch_frame(&mut self) {
+        self.catacomb.on_touch_frame();
+    }
+
+    /// Perform a single-finger swipe between two points.
+    pub fn swipe(
+        &mut self,
+        start: impl Into<Point<f64, Logical>>,
+        end: impl Into<Point<f64, Logical>>,
+    ) {
+        self.touch_down(0, start);
+        self.touch_frame();
+        self.touch_motion(0, end);
+        self.touch_frame();
+        self.touch_up(0);
+        self.touch_frame();
+    }
+
+    /// Queue a touch event at the current fake time.
+    fn queue_touch(&mut self, ty: TouchEventType, slot: u32, position: Point<f64, Logical>) {
+        let event = TouchEvent::new(ty, TouchSlot::from(Some(slot)), self.time(), position);
+        self.catacomb.queue_touch_event(event);
+    }
+
+    /// Input event timestamp at the current fake time.
+    fn time(&self) -> u32 {
+        clock::elapsed(self.start).as_millis() as u32
+    }
+}
+
+/// Backend without any input or output devices.
+pub struct TestBackend;
+
+impl Backend for TestBackend {
+    fn seat_name(&self) -> String {
+        String::from("seat-test")
+    }
+}
+
+/// Renderer which skips drawing entirely.
+struct TestRenderer;
+
+impl Render for TestRenderer {
+    fn render<B, F>(
+        &mut self,
+        _catacomb: &mut Catacomb<B>,
+        _draw_fun: F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(&mut Catacomb<B>, &mut Gles2Renderer, &mut Gles2Frame, u8),
+    {
+        Ok(())
+    }
+}
+
+/// Client-side configure state.
+#[derive(Default, Debug)]
+pub struct SurfaceState {
+    /// Size of the last acknowledged configure.
+    pub size: Option<(i32, i32)>,
+    /// Stop acknowledging configure events.
+    pub ignore_configures: bool,
+    /// Surface was closed or dismissed by the compositor.
+    pub closed: bool,
+
+    /// Size of the configure which is currently being received.
+    pending_size: (i32, i32),
+}
+
+/// Client-side XDG toplevel.
+pub struct TestToplevel {
+    pub state: Rc<RefCell<SurfaceState>>,
+    surface: Main<WlSurface>,
+    xdg_surface: Main<XdgSurface>,
+}
+
+impl TestToplevel {
+    /// Size of the last acknowledged configure.
+    pub fn size(&self) -> Option<(i32, i32)> {
+        self.state.borrow().size
+    }
+
+    /// Check if a compositor surface belongs to this toplevel.
+    pub fn is(&self, surface: &ServerSurface) -> bool {
+        surface.as_ref().id() == self.id()
+    }
+
+    /// Protocol ID of the toplevel's surface.
+    pub fn id(&self) -> u32 {
+        self.surface.as_ref().id()
+    }
+}
+
+/// Client-side XDG popup.
+pub struct TestPopup {
+    pub state: Rc<RefCell<SurfaceState>>,
+}
+
+/// Client-side layer shell surface.
+pub struct TestLayer {
+    pub state: Rc<RefCell<SurfaceState>>,
+}
+
+/// Touch event received by the client.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ClientTouchEvent {
+    Down { id: i32, position: (f64, f64) },
+    Motion { id: i32, position: (f64, f64) },
+    Up { id: i32 },
+    Cancel,
+}
+
+/// Client-side pointer events.
+#[derive(Default, Debug)]
+pub struct PointerState {
+    /// Protocol ID of the surface with pointer focus.
+    pub focus: Option<u32>,
+    /// All received button presses and releases.
+    pub buttons: Vec<(u32, wl_pointer::ButtonState)>,
+}
+
+/// Wayland client connection.
+struct TestClient {
+    display: Display,
+    attached: Attached<WlDisplay>,
+    queue: EventQueue,
+    globals: GlobalManager,
+}
+
+impl TestClient {
+    fn new(socket: UnixStream) -> Self {
+        let display = unsafe { Display::from_fd(socket.into_raw_fd()) }.expect("connect client");
+        let queue = display.create_event_queue();
+        let attached = (*display).clone().attach(queue.token());
+        let globals = GlobalManager::new(&attached);
+
+        Self { display, attached, queue, globals }
+    }
+
+    /// Read and dispatch all available events.
+    ///
+    /// Returns the number of dispatched events.
+    fn dispatch(&mut self) -> u32 {
+        if let Some(guard) = self.queue.prepare_read() {
+            if let Err(err) = guard.read_events() {
+                assert_eq!(err.kind(), ErrorKind::WouldBlock, "client read error: {err}");
+            }
+        }
+
+        self.queue.dispatch_pending(&mut (), |_, _, _| {}).expect("client dispatch")
+    }
+}
+
+/// Client-side protocol globals.
+struct Globals {
+    compositor: Main<WlCompositor>,
+    wm_base: Main<XdgWmBase>,
+    layer_shell: Main<ZwlrLayerShellV1>,
+    seat: Main<WlSeat>,
+    shm: Main<WlShm>,
+}
+
+impl Globals {
+    fn new(globals: &GlobalManager) -> Self {
+        let compositor = globals.instantiate_range(1, 4).expect("bind wl_compositor");
+        let wm_base: Main<XdgWmBase> = globals.instantiate_range(1, 2).expect("bind xdg_wm_base");
+        let layer_shell = globals.instantiate_range(1, 1).expect("bind zwlr_layer_shell_v1");
+        let seat = globals.instantiate_range(1, 5).expect("bind wl_seat");
+        let shm = globals.instantiate_exact(1).expect("bind wl_shm");
+
+        // Keep the client responsive.
+        wm_base.quick_assign(|wm_base, event, _| {
+            if let xdg_wm_base::Event::Ping { serial } = event {
+                wm_base.pong(serial);
+            }
+        });
+
+        Self { compositor, wm_base, layer_shell, seat, shm }
+    }
+}
+
+/// Acknowledge XDG configures and commit the surface.
+fn ack_xdg_configures(
+    xdg_surface: &Main<XdgSurface>,
+    surface: &Main<WlSurface>,
+    state: &Rc<RefCell<SurfaceState>>,
+) {
+    let surface = surface.clone();
+    let state = state.clone();
+    xdg_surface.quick_assign(move |xdg_surface, event, _| {
+        if let xdg_surface::Event::Configure { serial } = event {
+            let mut state = state.borrow_mut();
+            if !state.ignore_configures {
+                xdg_surface.ack_configure(serial);
+                state.size = Some(state.pending_size);
+                surface.commit();
+            }
+        }
+    });
+}
+
+/// Dispatch compositor and client until a sync request yields no other events.
+fn roundtrip(
+    catacomb: &mut Catacomb<TestBackend>,
+    event_loop: &mut EventLoop<'static, Catacomb<TestBackend>>,
+    client: &mut TestClient,
+) {
+    for _ in 0..MAX_ROUNDTRIPS {
+        let done = Rc::new(Cell::new(false));
+        let sync_done = done.clone();
+        client.attached.sync().quick_assign(move |_, _, _| sync_done.set(true));
+
+        let mut dispatched = 0;
+        for _ in 0..MAX_ROUNDTRIPS {
+            client.display.flush().expect("client flush");
+
+            event_loop.dispatch(Duration::ZERO, catacomb).expect("compositor dispatch");
+            catacomb.create_frame(TestRenderer);
+            let display = catacomb.display.clone();
+            display.borrow_mut().flush_clients(catacomb);
+
+            dispatched += client.dispatch();
+            if done.get() {
+                break;
+            }
+        }
+        assert!(done.get(), "compositor did not respond to sync request");
+
+        // Stop once the sync callback was the only event received.
+        if dispatched <= 1 {
+            return;
+        }
+    }
+
+    panic!("compositor did not settle after {MAX_ROUNDTRIPS} roundtrips");
+}
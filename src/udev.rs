@@ -1,13 +1,17 @@
 use std::error::Error as StdError;
-use std::os::unix::io::RawFd;
-use std::path::PathBuf;
+use std::mem;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use catacomb_ipc::OutputMode;
+use log::{debug, error, info, warn};
 use smithay::backend::allocator::dmabuf::Dmabuf;
 use smithay::backend::allocator::gbm::GbmDevice;
 use smithay::backend::drm::{DevPath, DrmDevice, DrmEvent, GbmBufferedSurface};
 use smithay::backend::egl::context::EGLContext;
 use smithay::backend::egl::display::EGLDisplay;
+use smithay::backend::input::{Device as _, InputEvent};
 use smithay::backend::libinput::{LibinputInputBackend, LibinputSessionInterface};
 use smithay::backend::renderer::gles2::{Gles2Frame, Gles2Renderer};
 use smithay::backend::renderer::{Bind, ImportDma, ImportEgl, Renderer};
@@ -15,68 +19,111 @@ use smithay::backend::session::auto::AutoSession;
 use smithay::backend::session::{Session, Signal};
 use smithay::backend::udev;
 use smithay::backend::udev::{UdevBackend, UdevEvent};
+use smithay::reexports::calloop::generic::Generic;
 use smithay::reexports::calloop::timer::{Timer, TimerHandle};
-use smithay::reexports::calloop::{Dispatcher, EventLoop, LoopHandle, RegistrationToken};
-use smithay::reexports::drm::control::connector::State as ConnectorState;
-use smithay::reexports::drm::control::Device;
-use smithay::reexports::input::Libinput;
+use smithay::reexports::calloop::{
+    Dispatcher, EventLoop, Interest, LoopHandle, Mode as TriggerMode, PostAction, RegistrationToken,
+};
+use smithay::reexports::drm::control::connector::{self, Interface};
+use smithay::reexports::drm::control::{crtc, Device as _, Mode as DrmMode, ModeTypeFlags};
+use smithay::reexports::input::{
+    AccelProfile as LibinputAccelProfile, Device as LibinputDevice, Libinput,
+};
 use smithay::reexports::nix::fcntl::OFlag;
 use smithay::reexports::nix::sys::stat::dev_t as DeviceId;
 use smithay::reexports::wayland_server::protocol::wl_output::Subpixel;
 use smithay::utils::signaling::{Linkable, SignalToken, Signaler};
+use smithay::utils::Transform;
 use smithay::wayland::dmabuf;
 use smithay::wayland::output::{Mode, PhysicalProperties};
 
-use crate::catacomb::{Backend, Catacomb, Render};
+use crate::catacomb::{Backend, Catacomb, Render, Startup};
+use crate::config::{AccelProfile, Config, DeviceConfig};
+use crate::evdev::{self, KeypadSlide};
+use crate::input::HardwareSwitch;
+use crate::logging;
 use crate::output::Output;
 
-pub fn run() {
-    let mut event_loop = EventLoop::try_new().expect("event loop");
-    let udev = Udev::new(event_loop.handle());
-    let mut catacomb = Catacomb::new(&mut event_loop, udev);
+/// DPMS property value of a powered on connector.
+const DPMS_ON: u64 = 0;
+
+/// DPMS property value of a powered off connector.
+const DPMS_OFF: u64 = 3;
+
+pub fn run(config: Config, startup: Startup) -> Result<(), Box<dyn StdError>> {
+    let mut event_loop = EventLoop::try_new()?;
+    let udev = Udev::new(event_loop.handle())?;
+    let mut catacomb = Catacomb::new(&mut event_loop, config, startup, udev)?;
 
     // Create backend and add presently connected devices.
-    let backend = UdevBackend::new(&catacomb.seat_name, None).expect("init udev");
+    let backend = UdevBackend::new(&catacomb.seat_name, logging::smithay())?;
     for (_, path) in backend.device_list() {
-        let _ = catacomb.add_device(path.into());
+        catacomb.add_device(path.into());
     }
 
     // Setup hardware acceleration.
-    let output_device = catacomb.backend.output_device.as_ref();
-    let formats = output_device.map(|device| device.renderer.dmabuf_formats().cloned().collect());
+    let gpu_device = catacomb.backend.gpu_device();
+    let formats = gpu_device.map(|device| device.renderer.dmabuf_formats().cloned().collect());
     dmabuf::init_dmabuf_global(
         &mut catacomb.display.borrow_mut(),
         formats.unwrap_or_default(),
         |buffer, mut data| {
             let catacomb = data.get::<Catacomb<Udev>>().unwrap();
-            let output_device = catacomb.backend.output_device.as_mut();
-            output_device
-                .and_then(|device| device.renderer.import_dmabuf(buffer, None).ok())
-                .is_some()
+            let gpu_device = catacomb.backend.gpu_device();
+            gpu_device.and_then(|device| device.renderer.import_dmabuf(buffer, None).ok()).is_some()
         },
-        None,
+        logging::smithay(),
     );
 
     // Setup input handling.
 
     let session = catacomb.backend.session.clone();
     let mut context = Libinput::new_with_udev::<LibinputSessionInterface<_>>(session.into());
-    context.udev_assign_seat(&catacomb.seat_name).expect("assign seat");
+    context
+        .udev_assign_seat(&catacomb.seat_name)
+        .map_err(|_| format!("Unable to assign seat {:?}", catacomb.seat_name))?;
 
-    let mut input_backend = LibinputInputBackend::new(context, None);
+    let mut input_backend = LibinputInputBackend::new(context, logging::smithay());
     input_backend.link(catacomb.backend.signaler.clone());
     event_loop
         .handle()
-        .insert_source(input_backend, |event, _, catacomb| catacomb.handle_input(event))
+        .insert_source(input_backend, |event, _, catacomb| {
+            let added_device = match &event {
+                InputEvent::DeviceAdded { device } => Some(device.clone()),
+                _ => None,
+            };
+
+            catacomb.handle_input(event);
+
+            // Apply libinput settings to new devices, using their matched config.
+            if let Some(mut device) = added_device {
+                if let Some(config) = catacomb.device_configs.get(&device.id()) {
+                    configure_device(&mut device, config);
+                }
+            }
+        })
         .expect("insert input source");
 
+    // Libinput does not report keypad slide switches, so they're read from evdev.
+    catacomb.add_keypad_slides();
+
+    // Reopen switches revoked while the session was inactive.
+    let handle = catacomb.backend.handle.clone();
+    let _keypad_slide_token = catacomb.backend.signaler.register(move |signal| {
+        if let Signal::ActivateSession = signal {
+            handle.insert_idle(|catacomb| {
+                if catacomb.backend.keypad_slides == 0 {
+                    catacomb.add_keypad_slides();
+                }
+            });
+        }
+    });
+
     // Handle device events.
     event_loop
         .handle()
         .insert_source(backend, move |event, _, catacomb| match event {
-            UdevEvent::Added { path, .. } => {
-                let _ = catacomb.add_device(path);
-            },
+            UdevEvent::Added { path, .. } => catacomb.add_device(path),
             UdevEvent::Changed { device_id } => catacomb.change_device(device_id),
             UdevEvent::Removed { device_id } => catacomb.remove_device(device_id),
         })
@@ -86,27 +133,94 @@ pub fn run() {
     let display = catacomb.display.clone();
     loop {
         if let Err(error) = event_loop.dispatch(None, &mut catacomb) {
-            eprintln!("Event loop error: {}", error);
+            error!("Event loop error: {error}");
             break;
         }
         display.borrow_mut().flush_clients(&mut catacomb);
     }
+
+    Ok(())
+}
+
+/// Change the DPMS state of a DRM connector.
+fn set_dpms(
+    drm: &DrmDevice<RawFd>,
+    connector: connector::Handle,
+    on: bool,
+) -> Result<(), Box<dyn StdError>> {
+    let properties = drm.get_properties(connector)?;
+    let (handles, _) = properties.as_props_and_values();
+    for &handle in handles {
+        if drm.get_property(handle)?.name().to_bytes() == b"DPMS" {
+            let value = if on { DPMS_ON } else { DPMS_OFF };
+            drm.set_property(connector, handle, value)?;
+            return Ok(());
+        }
+    }
+
+    Err("missing DPMS property".into())
+}
+
+/// Apply device settings to a libinput device.
+fn configure_device(device: &mut LibinputDevice, config: &DeviceConfig) {
+    let name = device.name().to_owned();
+
+    if let Some(matrix) = config.calibration_matrix {
+        if !device.config_calibration_has_matrix() {
+            warn!("Input device {name:?} does not support calibration");
+        } else if let Err(err) = device.config_calibration_set_matrix(matrix) {
+            warn!("Unable to set calibration matrix of {name:?}: {err:?}");
+        }
+    }
+
+    if let Some(enabled) = config.tap_to_click {
+        if device.config_tap_finger_count() > 0 {
+            if let Err(err) = device.config_tap_set_enabled(enabled) {
+                warn!("Unable to configure tap-to-click of {name:?}: {err:?}");
+            }
+        }
+    }
+
+    if let Some(enabled) = config.natural_scroll {
+        if device.config_scroll_has_natural_scroll() {
+            if let Err(err) = device.config_scroll_set_natural_scroll_enabled(enabled) {
+                warn!("Unable to configure natural scrolling of {name:?}: {err:?}");
+            }
+        }
+    }
+
+    if let Some(profile) = config.accel_profile {
+        let profile = match profile {
+            AccelProfile::Flat => LibinputAccelProfile::Flat,
+            AccelProfile::Adaptive => LibinputAccelProfile::Adaptive,
+        };
+
+        if device.config_accel_is_available() {
+            if let Err(err) = device.config_accel_set_profile(profile) {
+                warn!("Unable to set acceleration profile of {name:?}: {err:?}");
+            }
+        }
+    }
 }
 
 /// Udev backend shared state.
 pub struct Udev {
     handle: LoopHandle<'static, Catacomb<Udev>>,
-    output_device: Option<OutputDevice>,
-    render_timer: TimerHandle<DeviceId>,
+    render_timer: TimerHandle<(DeviceId, crtc::Handle)>,
     signaler: Signaler<Signal>,
+    devices: Vec<Device>,
     session: AutoSession,
     gpu: Option<PathBuf>,
+
+    /// Number of tracked keypad slide switches.
+    keypad_slides: usize,
 }
 
 impl Udev {
-    fn new(handle: LoopHandle<'static, Catacomb<Udev>>) -> Self {
+    fn new(handle: LoopHandle<'static, Catacomb<Udev>>) -> Result<Self, Box<dyn StdError>> {
         // Initialize the VT session.
-        let (session, notifier) = AutoSession::new(None).expect("init session");
+        let (session, notifier) =
+            AutoSession::new(logging::smithay()).ok_or("Unable to initialize session")?;
         let signaler = notifier.signaler();
 
         // Register session with the event loop so objects can link to the signaler.
@@ -116,13 +230,38 @@ impl Udev {
         let gpu = udev::primary_gpu(session.seat()).ok().flatten();
 
         // Create timer for internally-triggered redraws.
-        let render_timer = Timer::new().expect("setup render timer");
+        let render_timer = Timer::new()?;
         let timer_handle = render_timer.handle();
         handle
-            .insert_source(render_timer, |device_id, _, catacomb| catacomb.render(device_id))
+            .insert_source(render_timer, |(device_id, crtc), _, catacomb| {
+                catacomb.render(device_id, crtc)
+            })
             .expect("setting up render timer callback");
 
-        Self { handle, signaler, session, gpu, render_timer: timer_handle, output_device: None }
+        Ok(Self {
+            handle,
+            signaler,
+            session,
+            gpu,
+            render_timer: timer_handle,
+            devices: Default::default(),
+            keypad_slides: Default::default(),
+        })
+    }
+
+    /// Device used for hardware acceleration.
+    fn gpu_device(&mut self) -> Option<&mut Device> {
+        let gpu = self.gpu.as_ref();
+        match self.devices.iter().position(|device| device.gbm.dev_path().as_ref() == gpu) {
+            Some(index) => Some(&mut self.devices[index]),
+            None => self.devices.first_mut(),
+        }
+    }
+
+    /// Check if any connector is driving the internal output.
+    fn has_internal_surface(&self) -> bool {
+        let mut surfaces = self.devices.iter().flat_map(|device| &device.surfaces);
+        surfaces.any(|surface| !surface.external)
     }
 }
 
@@ -134,163 +273,564 @@ impl Backend for Udev {
     fn change_vt(&mut self, vt: i32) {
         let _ = self.session.change_vt(vt);
     }
+
+    fn reload_modes(&mut self) {
+        self.handle.insert_idle(|catacomb| catacomb.apply_modes());
+    }
+
+    fn check_mode(&self, output: &str, mode: Option<OutputMode>) -> Result<(), String> {
+        let mode = match mode {
+            Some(mode) => mode,
+            None => return Ok(()),
+        };
+
+        for device in &self.devices {
+            let surface = match device.surfaces.iter().find(|surface| surface.name == output) {
+                Some(surface) => surface,
+                None => continue,
+            };
+
+            let drm = device.drm.as_source_ref();
+            let connector = drm
+                .get_connector(surface.connector)
+                .map_err(|err| format!("unable to query DRM connector {output}: {err}"))?;
+
+            return match matching_mode(connector.modes(), mode) {
+                Some(_) => Ok(()),
+                None => Err(format!("output {output} does not support mode {mode}")),
+            };
+        }
+
+        Ok(())
+    }
+
+    fn set_internal_power(&mut self, on: bool) {
+        for device in &self.devices {
+            let drm = device.drm.as_source_ref();
+            for surface in device.surfaces.iter().filter(|surface| !surface.external) {
+                if let Err(err) = set_dpms(&drm, surface.connector, on) {
+                    let state = if on { "on" } else { "off" };
+                    warn!("Unable to turn DRM connector {} {state}: {err}", surface.name);
+                }
+
+                // Restart rendering, which is paused while blanked.
+                if on {
+                    let (device_id, crtc) = (device.id, surface.crtc);
+                    self.handle.insert_idle(move |catacomb| catacomb.render(device_id, crtc));
+                }
+            }
+        }
+    }
 }
 
 impl Catacomb<Udev> {
-    fn add_device(&mut self, path: PathBuf) -> Result<(), Box<dyn StdError>> {
+    /// Open a DRM device and drive all of its connected connectors.
+    ///
+    /// Devices which fail to initialize are skipped.
+    fn add_device(&mut self, path: PathBuf) {
+        info!("Adding DRM device {path:?}");
+
+        if let Err(err) = self.try_add_device(path.clone()) {
+            error!("Unable to add DRM device {path:?}: {err}");
+        }
+    }
+
+    fn try_add_device(&mut self, path: PathBuf) -> Result<(), Box<dyn StdError>> {
         let open_flags = OFlag::O_RDWR | OFlag::O_CLOEXEC | OFlag::O_NOCTTY | OFlag::O_NONBLOCK;
         let device_fd = self.backend.session.open(&path, open_flags)?;
 
-        let mut drm = DrmDevice::new(device_fd, true, None)?;
+        let mut drm = DrmDevice::new(device_fd, true, logging::smithay())?;
         let gbm = GbmDevice::new(device_fd)?;
 
-        let display = EGLDisplay::new(&gbm, None)?;
-        let context = EGLContext::new(&display, None)?;
+        let display = EGLDisplay::new(&gbm, logging::smithay())?;
+        let context = EGLContext::new(&display, logging::smithay())?;
 
-        let mut renderer = unsafe { Gles2Renderer::new(context, None).expect("create renderer") };
+        let mut renderer = unsafe { Gles2Renderer::new(context, logging::smithay())? };
 
         // Initialize GPU for EGL rendering.
         if Some(path) == self.backend.gpu {
             let _ = renderer.bind_wl_display(&self.display.borrow());
         }
 
-        // Create the surface we will render to.
-        let gbm_surface = self.create_gbm_surface(&renderer, &drm, &gbm).ok_or("gbm surface")?;
-
         // Redraw when VT is focused.
         let device_id = drm.device_id();
         let handle = self.backend.handle.clone();
         let restart_token = self.backend.signaler.register(move |signal| match signal {
             Signal::ActivateSession | Signal::ActivateDevice { .. } => {
-                handle.insert_idle(move |catacomb| catacomb.render(device_id));
+                handle.insert_idle(move |catacomb| catacomb.render_device(device_id));
             },
             _ => {},
         });
 
         // Listen for VBlanks.
         drm.link(self.backend.signaler.clone());
-        let dispatcher = Dispatcher::new(drm, move |event, _, catacomb: &mut Catacomb<_>| {
+        let drm = Dispatcher::new(drm, move |event, _, catacomb: &mut Catacomb<_>| {
             match event {
-                DrmEvent::VBlank(_crtc) => catacomb.render(device_id),
-                DrmEvent::Error(error) => eprintln!("DRM error: {}", error),
+                DrmEvent::VBlank(crtc) => catacomb.render(device_id, crtc),
+                DrmEvent::Error(error) => error!("DRM error on device {device_id}: {error}"),
             };
         });
-        let token = self.backend.handle.register_dispatcher(dispatcher)?;
+        let token = self.backend.handle.register_dispatcher(drm.clone())?;
 
-        self.backend.output_device = Some(OutputDevice {
-            frame_interval: Duration::from_millis(self.output.frame_interval()),
+        let mut device = Device {
             timer: self.backend.render_timer.clone(),
             _restart_token: restart_token,
+            surfaces: Vec::new(),
             id: device_id,
-            gbm_surface,
             renderer,
             token,
+            drm,
             gbm,
-        });
+        };
+
+        // Drive every connected connector.
+        self.create_surfaces(&mut device);
+        self.backend.devices.push(device);
 
         // Kick-off rendering.
-        self.render(device_id);
+        self.render_device(device_id);
 
         Ok(())
     }
 
     fn remove_device(&mut self, device_id: DeviceId) {
-        let output_device = self.backend.output_device.take();
-        if let Some(mut output_device) = output_device.filter(|device| device.id == device_id) {
-            self.backend.handle.remove(output_device.token);
+        let index = match self.backend.devices.iter().position(|device| device.id == device_id) {
+            Some(index) => index,
+            None => return,
+        };
 
-            // Disable hardware acceleration when the GPU is removed.
-            if output_device.gbm.dev_path() == self.backend.gpu {
-                output_device.renderer.unbind_wl_display();
-            }
+        info!("Removing DRM device {device_id}");
+
+        let mut device = self.backend.devices.remove(index);
+        self.backend.handle.remove(device.token);
+
+        // Stop advertising outputs without a backing connector.
+        let surfaces = mem::take(&mut device.surfaces);
+        self.remove_surfaces(&mut device, surfaces);
+
+        // Disable hardware acceleration when the GPU is removed.
+        if device.gbm.dev_path() == self.backend.gpu {
+            device.renderer.unbind_wl_display();
         }
     }
 
+    /// Update outputs after connectors were plugged in or removed.
     fn change_device(&mut self, device_id: DeviceId) {
-        let device = self.backend.output_device.as_ref().filter(|dev| dev.id == device_id);
-        let path = device.and_then(|device| device.gbm.dev_path());
-        if let Some(path) = path {
-            self.remove_device(device_id);
-            let _ = self.add_device(path);
+        let index = match self.backend.devices.iter().position(|device| device.id == device_id) {
+            Some(index) => index,
+            None => return,
+        };
+
+        debug!("Rescanning connectors of DRM device {device_id}");
+
+        let mut device = self.backend.devices.remove(index);
+
+        // Remove surfaces for unplugged connectors.
+        let connectors = device.connected_connectors();
+        let is_connected = |surface: &OutputSurface| {
+            connectors.iter().any(|connector| connector.handle() == surface.connector)
+        };
+        let (surfaces, removed): (Vec<_>, Vec<_>) =
+            mem::take(&mut device.surfaces).into_iter().partition(is_connected);
+        device.surfaces = surfaces;
+        self.remove_surfaces(&mut device, removed);
+
+        // Add surfaces for new connectors.
+        self.create_surfaces(&mut device);
+
+        self.backend.devices.insert(index, device);
+
+        // Kick-off rendering for new surfaces.
+        self.render_device(device_id);
+    }
+
+    /// Remove rendering surfaces and their outputs.
+    ///
+    /// If the internal output's surface is removed, the first remaining
+    /// external output is promoted and all windows are moved to it. Without
+    /// any other output, the internal output is deactivated until a new
+    /// connector is plugged in, which then becomes the internal output.
+    fn remove_surfaces(&mut self, device: &mut Device, surfaces: Vec<OutputSurface>) {
+        let mut internal_removed = false;
+        for surface in surfaces {
+            if surface.external {
+                self.remove_external_output(&surface.name);
+            } else {
+                internal_removed = true;
+            }
+        }
+
+        if !internal_removed {
+            return;
+        }
+
+        // Promote the first remaining output, keeping the windows mapped.
+        let promoted = {
+            let other_surfaces = self.backend.devices.iter_mut().flat_map(|dev| &mut dev.surfaces);
+            let mut surfaces = device.surfaces.iter_mut().chain(other_surfaces);
+            surfaces.find(|surface| surface.external).map(|surface| {
+                surface.external = false;
+                surface.name.clone()
+            })
+        };
+        match promoted {
+            Some(name) => self.promote_external_output(&name),
+            None => self.deactivate_internal_output(),
         }
     }
 
-    /// Create a new GBM surface for a device.
-    fn create_gbm_surface(
+    /// Create rendering surfaces for all newly connected connectors.
+    fn create_surfaces(&mut self, device: &mut Device) {
+        let mut connectors = device.connected_connectors();
+
+        if connectors.is_empty() {
+            warn!("No connected DRM connector found on device {}", device.id);
+        }
+
+        // Ignore connectors which are already in use.
+        connectors.retain(|connector| {
+            device.surfaces.iter().all(|surface| surface.connector != connector.handle())
+        });
+
+        // Prefer built-in panels as internal output.
+        connectors.sort_by_key(|connector| !is_internal(connector));
+
+        for connector in connectors {
+            let external = self.backend.has_internal_surface()
+                || device.surfaces.iter().any(|surface| !surface.external);
+
+            if let Some(surface) = self.create_surface(device, &connector, external) {
+                device.surfaces.push(surface);
+            }
+        }
+    }
+
+    /// Create a new rendering surface for a connector.
+    fn create_surface(
         &mut self,
-        renderer: &Gles2Renderer,
-        drm: &DrmDevice<RawFd>,
-        gbm: &GbmDevice<RawFd>,
-    ) -> Option<GbmBufferedSurface<GbmDevice<RawFd>, RawFd>> {
-        let formats = Bind::<Dmabuf>::supported_formats(renderer)?;
+        device: &Device,
+        connector: &connector::Info,
+        external: bool,
+    ) -> Option<OutputSurface> {
+        let output_name = format!("{:?}-{}", connector.interface(), connector.interface_id());
+
+        let requested_mode = self.output_mode(&output_name);
+        let connector_mode = match select_mode(&output_name, connector.modes(), requested_mode) {
+            Some(mode) => mode,
+            None => {
+                warn!("DRM connector {output_name} has no modes");
+                return None;
+            },
+        };
+
+        let formats = Bind::<Dmabuf>::supported_formats(&device.renderer)?;
+        let drm = device.drm.as_source_ref();
         let resources = drm.resource_handles().ok()?;
 
-        // Find the first connected output port.
-        let connector = resources.connectors().iter().find_map(|conn| {
-            drm.get_connector(*conn).ok().filter(|conn| conn.state() == ConnectorState::Connected)
-        })?;
-        let connector_mode = *connector.modes().get(0)?;
+        // Skip CRTCs already driving another connector.
+        let used_crtcs: Vec<_> = device.surfaces.iter().map(|surface| surface.crtc).collect();
 
-        let surface = connector
+        let (crtc, gbm_surface) = connector
             // Get all available encoders.
             .encoders()
             .iter()
             .flatten()
             .flat_map(|handle| drm.get_encoder(*handle))
-            // Get all CRTCs compatible with the encoder.
+            // Get all unused CRTCs compatible with the encoder.
             .flat_map(|encoder| resources.filter_crtcs(encoder.possible_crtcs()))
+            .filter(|crtc| !used_crtcs.contains(crtc))
             // Try to create a DRM surface.
-            .flat_map(|crtc| drm.create_surface(crtc, connector_mode, &[connector.handle()]))
+            .flat_map(|crtc| {
+                let surface = drm.create_surface(crtc, connector_mode, &[connector.handle()]);
+                surface.map(|surface| (crtc, surface))
+            })
             // Yield the first successful GBM buffer creation.
-            .find_map(|mut surface| {
+            .find_map(|(crtc, mut surface)| {
                 surface.link(self.backend.signaler.clone());
-                GbmBufferedSurface::new(surface, gbm.clone(), formats.clone(), None).ok()
+                let logger = logging::smithay();
+                let gbm_surface =
+                    GbmBufferedSurface::new(surface, device.gbm.clone(), formats.clone(), logger);
+                Some((crtc, gbm_surface.ok()?))
             })?;
 
-        let (width, height) = connector_mode.size();
-        let mode = Mode {
-            size: (width as i32, height as i32).into(),
-            refresh: connector_mode.vrefresh() as i32 * 1000,
-        };
-
+        let mode = output_mode(&connector_mode);
         let (physical_width, physical_height) = connector.size().unwrap_or((0, 0));
-        let output_name = format!("{:?}", connector.interface());
 
-        let mut display = self.display.borrow_mut();
-        self.output = Output::new(&mut display, output_name, mode, PhysicalProperties {
+        info!("Using DRM connector {output_name} with mode {}", mode_name(&connector_mode));
+
+        let properties = PhysicalProperties {
             size: (physical_width as i32, physical_height as i32).into(),
             subpixel: Subpixel::Unknown,
             model: "Generic DRM".into(),
             make: "Catacomb".into(),
-        });
+        };
+        let mut display = self.display.borrow_mut();
+        let scale = self.config.output.scale;
+        let output = Output::new(&mut display, output_name.clone(), mode, properties, scale);
+        drop(display);
 
-        Some(surface)
+        let frame_interval = Duration::from_millis(output.frame_interval());
+
+        if external {
+            self.add_external_output(output);
+        } else {
+            self.replace_internal_output(output);
+        }
+
+        Some(OutputSurface {
+            connector: connector.handle(),
+            name: output_name,
+            frame_interval,
+            gbm_surface,
+            external,
+            crtc,
+        })
+    }
+
+    /// Render all outputs of a specific device.
+    fn render_device(&mut self, device_id: DeviceId) {
+        let device = self.backend.devices.iter().find(|device| device.id == device_id);
+        let crtcs: Vec<_> =
+            device.iter().flat_map(|device| &device.surfaces).map(|s| s.crtc).collect();
+        for crtc in crtcs {
+            self.render(device_id, crtc);
+        }
+    }
+
+    /// Render a specific output.
+    fn render(&mut self, device_id: DeviceId, crtc: crtc::Handle) {
+        let mut devices = mem::take(&mut self.backend.devices);
+
+        let device = devices.iter_mut().find(|device| device.id == device_id);
+        if let Some(Device { renderer, surfaces, timer, .. }) = device {
+            // Client buffers are only imported into the internal output's renderer.
+            let shared_renderer = surfaces.iter().any(|surface| !surface.external);
+
+            if let Some(surface) = surfaces.iter_mut().find(|surface| surface.crtc == crtc) {
+                let (name, external) = (surface.name.clone(), surface.external);
+                let frame = SurfaceFrame { renderer, surface, timer, device_id };
+                if external {
+                    self.create_external_frame(&name, shared_renderer, frame);
+                } else {
+                    self.create_frame(frame);
+                }
+            }
+        }
+
+        self.backend.devices = devices;
     }
 
-    /// Render a specific device.
-    fn render(&mut self, device_id: DeviceId) {
-        let mut device = self.backend.output_device.take();
-        if let Some(device) = device.as_mut().filter(|device| device.id == device_id) {
-            self.create_frame(device);
+    /// Track all keypad slide switches.
+    fn add_keypad_slides(&mut self) {
+        let paths = match evdev::switch_devices() {
+            Ok(paths) => paths,
+            Err(err) => {
+                error!("Unable to enumerate switch devices: {err}");
+                return;
+            },
+        };
+
+        for path in paths {
+            if let Err(err) = self.add_keypad_slide(&path) {
+                warn!("Unable to add switch device {path:?}: {err}");
+            }
         }
-        self.backend.output_device = device;
+    }
+
+    /// Track a switch device if it has a keypad slide switch.
+    fn add_keypad_slide(&mut self, path: &Path) -> Result<(), Box<dyn StdError>> {
+        let open_flags = OFlag::O_RDONLY | OFlag::O_CLOEXEC | OFlag::O_NONBLOCK;
+        let fd = self.backend.session.open(path, open_flags)?;
+
+        let keypad_slide = match KeypadSlide::new(fd) {
+            Some(keypad_slide) => keypad_slide,
+            None => {
+                let _ = self.backend.session.close(fd);
+                return Ok(());
+            },
+        };
+
+        let on = match keypad_slide.state() {
+            Ok(on) => on,
+            Err(err) => {
+                let _ = self.backend.session.close(fd);
+                return Err(err.into());
+            },
+        };
+
+        info!("Adding keypad slide switch {path:?}");
+
+        let source = Generic::new(keypad_slide, Interest::READ, TriggerMode::Level);
+        self.backend
+            .handle
+            .insert_source(source, |_, keypad_slide, catacomb| {
+                match keypad_slide.read_events() {
+                    Ok(Some(on)) => catacomb.on_switch(HardwareSwitch::KeypadSlide, on),
+                    Ok(None) => (),
+                    // Stop reading from removed or revoked devices.
+                    Err(err) => {
+                        debug!("Removing keypad slide switch: {err}");
+                        let _ = catacomb.backend.session.close(keypad_slide.as_raw_fd());
+                        catacomb.backend.keypad_slides -= 1;
+                        return Ok(PostAction::Remove);
+                    },
+                }
+                Ok(PostAction::Continue)
+            })
+            .expect("insert keypad slide source");
+        self.backend.keypad_slides += 1;
+
+        self.on_switch(HardwareSwitch::KeypadSlide, on);
+
+        Ok(())
+    }
+
+    /// Switch all connectors to their requested modes.
+    fn apply_modes(&mut self) {
+        let mut devices = mem::take(&mut self.backend.devices);
+
+        for device in &mut devices {
+            let drm = device.drm.as_source_ref();
+            for surface in &mut device.surfaces {
+                let connector = match drm.get_connector(surface.connector) {
+                    Ok(connector) => connector,
+                    Err(err) => {
+                        warn!("Unable to query DRM connector {}: {err}", surface.name);
+                        continue;
+                    },
+                };
+
+                let requested_mode = self.output_mode(&surface.name);
+                let mode = match select_mode(&surface.name, connector.modes(), requested_mode) {
+                    Some(mode) if mode != surface.gbm_surface.current_mode() => mode,
+                    _ => continue,
+                };
+
+                if let Err(err) = surface.gbm_surface.use_mode(mode) {
+                    error!("Unable to switch {} to {}: {err}", surface.name, mode_name(&mode));
+                    continue;
+                }
+
+                info!("Switched DRM connector {} to mode {}", surface.name, mode_name(&mode));
+
+                // Resize clients and update the redraw rate.
+                self.set_output_mode(&surface.name, output_mode(&mode));
+                if let Some(output) = self.output_by_name(&surface.name) {
+                    surface.frame_interval = Duration::from_millis(output.frame_interval());
+                }
+            }
+        }
+
+        self.backend.devices = devices;
     }
 }
 
-/// Target device for rendering.
-struct OutputDevice {
-    gbm_surface: GbmBufferedSurface<GbmDevice<RawFd>, RawFd>,
-    timer: TimerHandle<DeviceId>,
+impl Device {
+    /// Get all connectors with an attached display.
+    fn connected_connectors(&self) -> Vec<connector::Info> {
+        let drm = self.drm.as_source_ref();
+        let resources = match drm.resource_handles() {
+            Ok(resources) => resources,
+            Err(err) => {
+                warn!("Unable to query DRM resources of device {}: {err}", self.id);
+                return Vec::new();
+            },
+        };
+
+        resources
+            .connectors()
+            .iter()
+            .flat_map(|connector| drm.get_connector(*connector))
+            .filter(|connector| connector.state() == connector::State::Connected)
+            .collect()
+    }
+}
+
+/// Pick the mode for a connector.
+///
+/// The requested mode is used if the connector supports it, otherwise the
+/// connector's preferred mode is used instead.
+fn select_mode(name: &str, modes: &[DrmMode], requested: Option<OutputMode>) -> Option<DrmMode> {
+    if let Some(requested) = requested {
+        match matching_mode(modes, requested) {
+            Some(mode) => return Some(mode),
+            None => warn!("DRM connector {name} does not support mode {requested}"),
+        }
+    }
+
+    let preferred = modes.iter().find(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED));
+    preferred.or_else(|| modes.first()).copied()
+}
+
+/// Find the connector mode matching a requested mode.
+///
+/// The highest refresh rate is used when none was requested.
+fn matching_mode(modes: &[DrmMode], requested: OutputMode) -> Option<DrmMode> {
+    modes
+        .iter()
+        .filter(|mode| {
+            let (width, height) = mode.size();
+            (width as u32, height as u32) == (requested.width, requested.height)
+                && requested.refresh.map_or(true, |refresh| mode.vrefresh() == refresh)
+        })
+        .max_by_key(|mode| mode.vrefresh())
+        .copied()
+}
+
+/// Convert a DRM mode to a Wayland output mode.
+fn output_mode(mode: &DrmMode) -> Mode {
+    let (width, height) = mode.size();
+    Mode { size: (width as i32, height as i32).into(), refresh: mode.vrefresh() as i32 * 1000 }
+}
+
+/// Human-readable DRM mode description.
+fn mode_name(mode: &DrmMode) -> String {
+    let (width, height) = mode.size();
+    format!("{width}x{height}@{}Hz", mode.vrefresh())
+}
+
+/// Check if a connector is a built-in display panel.
+fn is_internal(connector: &connector::Info) -> bool {
+    matches!(
+        connector.interface(),
+        Interface::EmbeddedDisplayPort | Interface::LVDS | Interface::DSI
+    )
+}
+
+/// DRM device with all its active connectors.
+struct Device {
+    drm: Dispatcher<'static, DrmDevice<RawFd>, Catacomb<Udev>>,
+    timer: TimerHandle<(DeviceId, crtc::Handle)>,
+    surfaces: Vec<OutputSurface>,
     gbm: GbmDevice<RawFd>,
     renderer: Gles2Renderer,
     id: DeviceId,
 
     _restart_token: SignalToken,
-    frame_interval: Duration,
     token: RegistrationToken,
 }
 
-impl Render for &mut OutputDevice {
+/// Rendering surface for a single connector.
+struct OutputSurface {
+    gbm_surface: GbmBufferedSurface<GbmDevice<RawFd>, RawFd>,
+    connector: connector::Handle,
+    frame_interval: Duration,
+    crtc: crtc::Handle,
+    name: String,
+
+    /// Whether this surface is driving an external output.
+    external: bool,
+}
+
+/// Frame for a single output surface.
+struct SurfaceFrame<'a> {
+    timer: &'a TimerHandle<(DeviceId, crtc::Handle)>,
+    surface: &'a mut OutputSurface,
+    renderer: &'a mut Gles2Renderer,
+    device_id: DeviceId,
+}
+
+impl Render for SurfaceFrame<'_> {
     fn render<B, F>(
         &mut self,
         catacomb: &mut Catacomb<B>,
@@ -300,26 +840,34 @@ impl Render for &mut OutputDevice {
         F: FnOnce(&mut Catacomb<B>, &mut Gles2Renderer, &mut Gles2Frame, u8),
     {
         // Mark the current frame as submitted.
-        self.gbm_surface.frame_submitted()?;
+        self.surface.gbm_surface.frame_submitted()?;
 
         // Bind the next buffer to render into.
-        let (dmabuf, age) = self.gbm_surface.next_buffer()?;
+        let (dmabuf, age) = self.surface.gbm_surface.next_buffer()?;
         self.renderer.bind(dmabuf)?;
 
+        // Only the internal output follows the device orientation.
+        let (output_size, transform) = if self.surface.external {
+            let name = &self.surface.name;
+            let output = catacomb.external_output(name).ok_or("missing external output")?;
+            (output.physical_resolution(), Transform::Normal)
+        } else {
+            let transform = catacomb.windows.orientation().transform();
+            (catacomb.output.physical_resolution(), transform)
+        };
+
         // Draw the current frame into the buffer.
-        let transform = catacomb.windows.orientation().transform();
-        let output_size = catacomb.output.physical_resolution();
         self.renderer.render(output_size, transform, |renderer, frame| {
             draw_fun(catacomb, renderer, frame, age)
         })?;
 
         // Queue buffer for rendering.
-        self.gbm_surface.queue_buffer()?;
+        self.surface.gbm_surface.queue_buffer()?;
 
         Ok(())
     }
 
     fn reschedule(&mut self) {
-        self.timer.add_timeout(self.frame_interval, self.id);
+        self.timer.add_timeout(self.surface.frame_interval, (self.device_id, self.surface.crtc));
     }
 }
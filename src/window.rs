@@ -7,6 +7,8 @@ use std::rc::{Rc, Weak};
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
+use catacomb_ipc::{IpcEvent, View as IpcView, WindowInfo};
+use log::{debug, trace, warn};
 use smithay::backend::renderer::gles2::{Gles2Frame, Gles2Renderer};
 use smithay::backend::renderer::{self, BufferType, ImportAll};
 use smithay::reexports::wayland_protocols::unstable::xdg_decoration;
@@ -16,6 +18,7 @@ use smithay::utils::{Logical, Physical, Point, Rectangle, Size};
 use smithay::wayland::compositor::{
     self, SubsurfaceCachedState, SurfaceAttributes, SurfaceData, TraversalAction,
 };
+use smithay::wayland::seat::CursorImageAttributes;
 use smithay::wayland::shell::wlr_layer::{
     Anchor, ExclusiveZone, KeyboardInteractivity, Layer, LayerSurface, LayerSurfaceAttributes,
     LayerSurfaceCachedState, LayerSurfaceState,
@@ -27,6 +30,8 @@ use smithay::wayland::shell::xdg::{
 use xdg_decoration::v1::server::zxdg_toplevel_decoration_v1::Mode as DecorationMode;
 
 use crate::catacomb::Damage;
+use crate::clock;
+use crate::config::{Config, OverviewConfig};
 use crate::drawing::{Graphics, SurfaceBuffer, Texture};
 use crate::input::{Gesture, TouchState, HOLD_DURATION};
 use crate::layer::Layers;
@@ -65,31 +70,63 @@ pub struct Windows {
 
     /// Compositor damage beyond window-internal changes.
     fully_damaged: bool,
+
+    /// Windows already announced to IPC subscribers.
+    announced: Vec<(Weak<RefCell<Window>>, WindowInfo)>,
+
+    /// Exclusive space announced to IPC subscribers.
+    exclusive: ExclusiveSpace,
+
+    /// Pending IPC events for committed transactions.
+    events: Vec<IpcEvent>,
+
+    /// Windows tiled on the external output while docked.
+    desktop: Vec<Weak<RefCell<Window>>>,
+
+    /// Size of the docked desktop, `None` while undocked.
+    desktop_size: Option<Size<i32, Logical>>,
+
+    /// Desktop damage beyond window-internal changes.
+    desktop_damaged: bool,
 }
 
 impl Windows {
     pub fn new() -> Self {
         Self {
-            start_time: Instant::now(),
+            start_time: clock::now(),
             // By default everything is fully damaged.
             fully_damaged: true,
             orphan_popups: Default::default(),
             transaction: Default::default(),
+            announced: Default::default(),
+            exclusive: Default::default(),
             orientation: Default::default(),
             secondary: Default::default(),
             windows: Default::default(),
             primary: Default::default(),
             layers: Default::default(),
+            events: Default::default(),
             focus: Default::default(),
             view: Default::default(),
+            desktop: Default::default(),
+            desktop_size: Default::default(),
+            desktop_damaged: Default::default(),
         }
     }
 
     /// Add a new window.
-    pub fn add(&mut self, surface: ToplevelSurface, output: &Output) {
+    ///
+    /// While docked, new windows are placed on the `desktop` output instead.
+    pub fn add(&mut self, surface: ToplevelSurface, output: &Output, desktop: Option<&Output>) {
         self.windows.push(Rc::new(RefCell::new(Window::new(surface))));
-        self.set_primary(output, self.windows.len() - 1);
-        self.set_secondary(output, None);
+
+        match desktop.filter(|_| self.desktop_size.is_some()) {
+            Some(desktop) => self.add_to_desktop(output, desktop, self.windows.len() - 1),
+            None => {
+                self.set_primary(output, self.windows.len() - 1);
+                self.set_secondary(output, None);
+            },
+        }
     }
 
     /// Add a new layer shell window.
@@ -197,6 +234,7 @@ impl Windows {
         frame: &mut Gles2Frame,
         graphics: &mut Graphics,
         output: &Output,
+        config: &Config,
         damage: &[Rectangle<f64, Physical>],
     ) {
         // Reset damage.
@@ -210,10 +248,10 @@ impl Windows {
             },
             View::DragAndDrop(ref dnd) => {
                 self.with_visible(|window| window.draw(renderer, frame, output, 1., None, damage));
-                dnd.draw(renderer, frame, output, &self.windows, graphics);
+                dnd.draw(renderer, frame, output, config, &self.windows, graphics);
             },
             View::Overview(ref mut overview) => {
-                overview.draw(renderer, frame, output, &self.windows, graphics);
+                overview.draw(renderer, frame, output, config, &self.windows, graphics);
 
                 // Stage immediate redraw while overview animations are active.
                 if overview.animating_drag(self.windows.len()) {
@@ -225,10 +263,30 @@ impl Windows {
         self.layers.draw_foreground(renderer, frame, output, damage);
     }
 
+    /// Draw the docked desktop.
+    pub fn draw_desktop(
+        &mut self,
+        renderer: &mut Gles2Renderer,
+        frame: &mut Gles2Frame,
+        output: &Output,
+        damage: &[Rectangle<f64, Physical>],
+    ) {
+        self.desktop_damaged = false;
+
+        for window in self.desktop.iter().filter_map(Weak::upgrade) {
+            window.borrow_mut().draw(renderer, frame, output, 1., None, damage);
+        }
+    }
+
     /// Request new frames for all visible windows.
     pub fn request_frames(&mut self) {
+        let runtime = self.runtime();
+
+        for window in self.desktop.iter().filter_map(Weak::upgrade) {
+            window.borrow_mut().request_frame(runtime);
+        }
+
         if self.view == View::Workspace {
-            let runtime = self.runtime();
             self.layers.request_frames(runtime);
             self.with_visible(|window| window.request_frame(runtime));
         }
@@ -252,6 +310,19 @@ impl Windows {
             self.refresh_visible(output);
         }
 
+        // Retile the desktop after window death.
+        let dead_desktop_windows = self
+            .desktop
+            .iter()
+            .any(|window| window.upgrade().map_or(true, |window| !window.borrow().alive()));
+        if dead_desktop_windows {
+            let transaction = self.transaction.get_or_insert(Transaction::new(self));
+            transaction
+                .desktop
+                .retain(|window| window.upgrade().map_or(false, |window| window.borrow().alive()));
+            transaction.update_desktop_dimensions(self.desktop_size);
+        }
+
         // Cleanup old popup windows.
         for window in &mut self.windows {
             window.borrow_mut().refresh_popups();
@@ -259,7 +330,7 @@ impl Windows {
 
         // Start D&D on long touch in overview.
         if let View::Overview(overview) = &mut self.view {
-            if overview.hold_start.map_or(false, |start| start.elapsed() >= HOLD_DURATION) {
+            if overview.hold_start.map_or(false, |start| clock::elapsed(start) >= HOLD_DURATION) {
                 let index = overview.focused_index(self.windows.len());
                 let dnd = DragAndDrop::new(overview.last_drag_point, overview.x_offset, index);
                 self.view = View::DragAndDrop(dnd);
@@ -328,22 +399,26 @@ impl Windows {
     }
 
     /// Attempt to execute pending transactions.
-    pub fn update_transaction(&mut self) {
+    pub fn update_transaction(&mut self, output: &Output) {
         let transaction = match &mut self.transaction {
             Some(start) => start,
             None => return,
         };
 
-        // Check if the transaction requires updating.
-        if Instant::now().duration_since(transaction.start) <= MAX_TRANSACTION_DURATION {
-            // Check if all participants are ready.
-            let finished = self.windows.iter().all(|window| window.borrow().transaction_done())
-                && self.layers.iter().all(|window| window.transaction_done());
+        // Check if all participants are ready.
+        let pending = self.windows.iter().filter(|window| !window.borrow().transaction_done());
+        let pending_layers = self.layers.iter().filter(|window| !window.transaction_done());
+        let pending = pending.count() + pending_layers.count();
 
-            // Abort if the transaction is still pending.
-            if !finished {
+        // Abort if the transaction is still pending.
+        let elapsed = clock::elapsed(transaction.start);
+        if pending > 0 {
+            if elapsed <= MAX_TRANSACTION_DURATION {
+                trace!("Transaction waiting for {pending} surfaces");
                 return;
             }
+
+            warn!("Transaction timed out after {elapsed:?} with {pending} surfaces pending");
         }
 
         let secondary_index = self.primary.strong_count().max(1);
@@ -376,19 +451,110 @@ impl Windows {
 
         // Apply window management changes.
         let transaction = self.transaction.take().unwrap();
+        debug!(
+            "Committing transaction after {elapsed:?} (view: {:?}, orientation: {:?})",
+            transaction.view, transaction.orientation
+        );
+        self.queue_events(&transaction, output);
         self.view = transaction.view.unwrap_or(self.view);
         self.orientation = transaction.orientation;
         self.secondary = transaction.secondary;
         self.primary = transaction.primary;
+        self.desktop = transaction.desktop;
+        self.desktop_damaged = true;
         self.fully_damaged = true;
     }
 
+    /// Take all IPC events for committed transactions.
+    pub fn take_events(&mut self) -> Vec<IpcEvent> {
+        self.queue_window_changes();
+        mem::take(&mut self.events)
+    }
+
+    /// Queue IPC events for title and app ID changes of announced windows.
+    fn queue_window_changes(&mut self) {
+        for (window, info) in &mut self.announced {
+            let window = match window.upgrade() {
+                Some(window) => window,
+                None => continue,
+            };
+
+            let new_info = window.borrow().info();
+            if new_info != *info {
+                let previous = mem::replace(info, new_info.clone());
+                self.events.push(IpcEvent::WindowChanged { previous, window: new_info });
+            }
+        }
+    }
+
+    /// Queue IPC events for a transaction which is about to be committed.
+    fn queue_events(&mut self, transaction: &Transaction, output: &Output) {
+        // Announce destroyed windows.
+        let windows = &self.windows;
+        let events = &mut self.events;
+        self.announced.retain(|(weak, info)| {
+            let alive = windows.iter().any(|window| Rc::as_ptr(window) == weak.as_ptr());
+            if !alive {
+                events.push(IpcEvent::WindowDestroyed { window: info.clone() });
+            }
+            alive
+        });
+
+        // Announce new windows.
+        for window in &self.windows {
+            let known = self.announced.iter().any(|(weak, _)| weak.as_ptr() == Rc::as_ptr(window));
+            if !known {
+                let info = window.borrow().info();
+                self.events.push(IpcEvent::WindowCreated { window: info.clone() });
+                self.announced.push((Rc::downgrade(window), info));
+            }
+        }
+
+        if !transaction.primary.ptr_eq(&self.primary) {
+            let window = self.announced_info(&transaction.primary);
+            self.events.push(IpcEvent::PrimaryChanged { window });
+        }
+
+        if !transaction.secondary.ptr_eq(&self.secondary) {
+            let window = self.announced_info(&transaction.secondary);
+            self.events.push(IpcEvent::SecondaryChanged { window });
+        }
+
+        if let Some(view) = transaction.view.map(IpcView::from) {
+            if view != IpcView::from(self.view) {
+                self.events.push(IpcEvent::ViewChanged { view });
+            }
+        }
+
+        if transaction.orientation != self.orientation {
+            let orientation = transaction.orientation.into();
+            self.events.push(IpcEvent::OrientationChanged { orientation });
+        }
+
+        if output.exclusive != self.exclusive {
+            let ExclusiveSpace { top, right, bottom, left } = output.exclusive;
+            self.events.push(IpcEvent::ExclusiveSpaceChanged { top, right, bottom, left });
+            self.exclusive = output.exclusive;
+        }
+    }
+
+    /// IPC information for an announced window.
+    fn announced_info(&self, window: &Weak<RefCell<Window>>) -> Option<WindowInfo> {
+        self.announced
+            .iter()
+            .find(|(weak, _)| weak.ptr_eq(window) && window.strong_count() > 0)
+            .map(|(_, info)| info.clone())
+    }
+
     /// Resize all windows to their expected size.
     pub fn resize_all(&mut self, output: &mut Output) {
         let transaction = self.transaction.get_or_insert(Transaction::new(self));
 
         // Resize invisible windows.
-        for window in &self.windows {
+        let on_desktop = |window: &Rc<RefCell<Window>>| {
+            transaction.desktop.iter().any(|desktop| Rc::as_ptr(window) == desktop.as_ptr())
+        };
+        for window in self.windows.iter().filter(|window| !on_desktop(window)) {
             let mut window = window.borrow_mut();
             let rectangle = Rectangle::from_loc_and_size((0, 0), output.available().size);
             window.set_dimensions(transaction, rectangle);
@@ -397,6 +563,9 @@ impl Windows {
         // Resize primary/secondary.
         transaction.update_visible_dimensions(output);
 
+        // Resize desktop windows.
+        transaction.update_desktop_dimensions(self.desktop_size);
+
         // Resize layer shell windows.
         for window in self.layers.iter_mut() {
             window.update_dimensions(output, transaction);
@@ -410,6 +579,118 @@ impl Windows {
         self.resize_all(output);
     }
 
+    /// Force a redraw of the entire output.
+    pub fn damage_all(&mut self) {
+        self.fully_damaged = true;
+    }
+
+    /// Check if the docked desktop requires a redraw.
+    pub fn desktop_damaged(&self) -> bool {
+        self.desktop_damaged
+            || self.desktop.iter().filter_map(Weak::upgrade).any(|window| window.borrow().damaged())
+    }
+
+    /// Start tiling windows on an external output.
+    ///
+    /// This is also used to update the desktop after its output changed.
+    pub fn dock(&mut self, desktop: &Output) {
+        self.desktop_size = Some(desktop.size());
+
+        let transaction = self.transaction.get_or_insert(Transaction::new(self));
+        for window in transaction.desktop.iter().filter_map(Weak::upgrade) {
+            window.borrow().with_surfaces(|surface, _| desktop.enter(surface));
+        }
+        transaction.update_desktop_dimensions(self.desktop_size);
+    }
+
+    /// Move all desktop windows back to the internal output.
+    ///
+    /// The `desktop` output should be passed if it is still connected.
+    pub fn undock(&mut self, output: &mut Output, desktop: Option<&Output>) {
+        self.desktop_size = None;
+
+        let transaction = self.start_transaction();
+        for window in transaction.desktop.drain(..).filter_map(|window| window.upgrade()) {
+            if let Some(desktop) = desktop {
+                window.borrow().with_surfaces(|surface, _| desktop.leave(surface));
+            }
+        }
+
+        self.resize_all(output);
+    }
+
+    /// Move the focused window between the internal output and the desktop.
+    pub fn toggle_desktop(&mut self, output: &Output, desktop: &Output) {
+        if self.desktop_size.is_none() {
+            return;
+        }
+
+        let focus = self.focus.toplevel.upgrade().or_else(|| self.primary.upgrade());
+        let index = match focus.and_then(|focus| self.position(&Rc::downgrade(&focus))) {
+            Some(index) => index,
+            None => return,
+        };
+
+        let weak = Rc::downgrade(&self.windows[index]);
+        let transaction = self.start_transaction();
+        if transaction.desktop.iter().any(|window| window.ptr_eq(&weak)) {
+            self.windows[index].borrow().with_surfaces(|surface, _| desktop.leave(surface));
+            self.show_primary(output, index);
+        } else {
+            self.add_to_desktop(output, desktop, index);
+        }
+    }
+
+    /// Focus the next or previous desktop window.
+    pub fn cycle_desktop_focus(&mut self, forward: bool) {
+        let count = self.desktop.len();
+        if count == 0 {
+            return;
+        }
+
+        let focused = self.desktop.iter().position(|window| window.ptr_eq(&self.focus.toplevel));
+        let index = match focused {
+            Some(index) if forward => (index + 1) % count,
+            Some(index) => (index + count - 1) % count,
+            None => 0,
+        };
+
+        self.focus.layer = None;
+        self.focus.toplevel = self.desktop[index].clone();
+    }
+
+    /// Check which desktop surface is at a specific location.
+    ///
+    /// This will also focus the window containing the surface.
+    pub fn desktop_surface_at(&mut self, position: Point<f64, Logical>) -> Option<OffsetSurface> {
+        for window in self.desktop.iter().filter_map(Weak::upgrade) {
+            let window_ref = window.borrow();
+            if window_ref.contains(position) {
+                self.focus.layer = None;
+                self.focus.toplevel = Rc::downgrade(&window);
+                return window_ref.surface_at(position);
+            }
+        }
+
+        None
+    }
+
+    /// Request the focused window to close.
+    pub fn close_focused(&self) {
+        let focus = self.focus.toplevel.upgrade().or_else(|| self.primary.upgrade());
+        if let Some(window) = focus {
+            window.borrow().surface.send_close();
+        }
+    }
+
+    /// Dismiss the topmost popup of the focused window.
+    ///
+    /// Returns `false` if the window has no popups.
+    pub fn dismiss_popup(&self) -> bool {
+        let focus = self.focus.toplevel.upgrade().or_else(|| self.primary.upgrade());
+        focus.map_or(false, |window| window.borrow().dismiss_popup())
+    }
+
     /// Get the current rendering orientation.
     pub fn orientation(&self) -> Orientation {
         self.orientation
@@ -445,12 +726,17 @@ impl Windows {
     }
 
     /// Handle start of touch input.
-    pub fn on_touch_start(&mut self, output: &Output, point: Point<f64, Logical>) {
+    pub fn on_touch_start(
+        &mut self,
+        output: &Output,
+        config: &OverviewConfig,
+        point: Point<f64, Logical>,
+    ) {
         if let View::Overview(overview) = &mut self.view {
             // Click inside focused window stages it for opening as secondary.
-            let window_bounds = overview.focused_bounds(output, self.windows.len());
+            let window_bounds = overview.focused_bounds(output, config, self.windows.len());
             if window_bounds.contains(point.to_i32_round()) {
-                overview.hold_start = Some(Instant::now());
+                overview.hold_start = Some(clock::now());
             }
 
             overview.last_drag_point = point;
@@ -460,7 +746,7 @@ impl Windows {
     }
 
     /// Hand quick touch input.
-    pub fn on_tap(&mut self, output: &Output, point: Point<f64, Logical>) {
+    pub fn on_tap(&mut self, output: &Output, config: &OverviewConfig, point: Point<f64, Logical>) {
         let overview = match &mut self.view {
             View::Overview(overview) => overview,
             View::DragAndDrop(_) | View::Workspace => return,
@@ -469,7 +755,7 @@ impl Windows {
         overview.hold_start = None;
 
         // Click inside focused window opens it as primary.
-        let window_bounds = overview.focused_bounds(output, self.windows.len());
+        let window_bounds = overview.focused_bounds(output, config, self.windows.len());
         if !self.windows.is_empty() && window_bounds.contains(point.to_i32_round()) {
             let index = overview.focused_index(self.windows.len());
 
@@ -487,6 +773,7 @@ impl Windows {
     pub fn on_drag(
         &mut self,
         output: &Output,
+        config: &OverviewConfig,
         touch_state: &mut TouchState,
         mut point: Point<f64, Logical>,
     ) {
@@ -532,7 +819,7 @@ impl Windows {
         }
 
         // Cancel velocity once drag actions are completed.
-        if overview.should_close(output) || overview.overdrag_limited(self.windows.len()) {
+        if overview.should_close(output, config) || overview.overdrag_limited(self.windows.len()) {
             touch_state.cancel_velocity();
         }
 
@@ -543,13 +830,27 @@ impl Windows {
         self.fully_damaged = true;
     }
 
+    /// Scroll through the overview by a horizontal distance.
+    ///
+    /// Scrolling is completed by [`Self::on_drag_release`].
+    pub fn scroll_overview(&mut self, delta: f64) {
+        if let View::Overview(overview) = &mut self.view {
+            overview.x_offset += delta / OVERVIEW_HORIZONTAL_SENSITIVITY;
+            overview.last_overdrag_step = None;
+            overview.hold_start = None;
+
+            // Redraw when cycling through the overview.
+            self.fully_damaged = true;
+        }
+    }
+
     /// Handle touch drag release.
-    pub fn on_drag_release(&mut self, output: &Output) {
+    pub fn on_drag_release(&mut self, output: &Output, config: &OverviewConfig) {
         match self.view {
             View::Overview(ref mut overview) => {
-                let should_close = overview.should_close(output);
+                let should_close = overview.should_close(output, config);
 
-                overview.last_overdrag_step = Some(Instant::now());
+                overview.last_overdrag_step = Some(clock::now());
                 overview.y_offset = 0.;
 
                 // Close window if y offset exceeds the threshold.
@@ -566,7 +867,7 @@ impl Windows {
                 }
             },
             View::DragAndDrop(dnd) => {
-                let (primary_bounds, secondary_bounds) = dnd.drop_bounds(output);
+                let (primary_bounds, secondary_bounds) = dnd.drop_bounds(output, config);
                 if primary_bounds.to_f64().contains(dnd.touch_position) {
                     self.set_primary(output, dnd.window_index);
                     self.set_view(View::Workspace);
@@ -582,6 +883,24 @@ impl Windows {
         }
     }
 
+    /// Abort a touch drag without applying its result.
+    ///
+    /// Unlike [`Self::on_drag_release`], this never closes or moves any
+    /// windows.
+    pub fn cancel_drag(&mut self) {
+        match self.view {
+            View::Overview(ref mut overview) => {
+                overview.last_overdrag_step = Some(clock::now());
+                overview.y_offset = 0.;
+            },
+            View::DragAndDrop(dnd) => {
+                let overview = Overview { x_offset: dnd.overview_x_offset, ..Overview::new() };
+                self.set_view(View::Overview(overview));
+            },
+            View::Workspace => (),
+        }
+    }
+
     /// Handle touch gestures.
     pub fn on_gesture(&mut self, output: &Output, gesture: Gesture) {
         match (gesture, self.view) {
@@ -640,9 +959,102 @@ impl Windows {
         None
     }
 
+    /// Check which surface is below the pointer.
+    ///
+    /// In contrast to [`Self::touch_surface_at`], this will not change focus.
+    /// While `docked`, the position is relative to the desktop output.
+    pub fn pointer_surface_at(
+        &self,
+        position: Point<f64, Logical>,
+        docked: bool,
+    ) -> Option<OffsetSurface> {
+        if docked {
+            for window in self.desktop.iter().filter_map(Weak::upgrade) {
+                let window_ref = window.borrow();
+                if window_ref.contains(position) {
+                    return window_ref.surface_at(position);
+                }
+            }
+            return None;
+        }
+
+        // Prevent window interaction in Overview/DnD.
+        if self.view != View::Workspace {
+            return None;
+        }
+
+        if let Some(window) = self.layers.foreground_window_at(position) {
+            return window.surface_at(position);
+        }
+
+        for window in self.primary.upgrade().iter().chain(&self.secondary.upgrade()) {
+            let window_ref = window.borrow();
+            if window_ref.contains(position) {
+                return window_ref.surface_at(position);
+            }
+        }
+
+        self.layers.background_window_at(position).and_then(|window| window.surface_at(position))
+    }
+
+    /// Number of windows.
+    pub fn window_count(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// Show a window as primary in the workspace view.
+    pub fn show_primary(&mut self, output: &Output, index: usize) {
+        self.set_primary(output, index);
+        self.set_view(View::Workspace);
+    }
+
+    /// Switch to the next or previous window.
+    ///
+    /// The new window replaces both primary and secondary. Windows on the
+    /// docked desktop are skipped.
+    pub fn cycle_primary(&mut self, output: &Output, forward: bool) {
+        if self.view != View::Workspace {
+            return;
+        }
+
+        if let Some(index) = self.adjacent_window(forward) {
+            self.set_primary(output, index);
+            self.set_secondary(output, None);
+        }
+    }
+
+    /// Show or hide the secondary window.
+    ///
+    /// Without a secondary window, the window after the primary one is shown.
+    pub fn toggle_secondary(&mut self, output: &Output) {
+        if self.view != View::Workspace {
+            return;
+        }
+
+        let transaction = self.start_transaction();
+        if transaction.secondary.strong_count() > 0 {
+            self.set_secondary(output, None);
+        } else if transaction.primary.strong_count() > 0 {
+            if let Some(index) = self.adjacent_window(true) {
+                self.set_secondary(output, index);
+            }
+        }
+    }
+
+    /// Show a window as secondary in the workspace view.
+    pub fn show_secondary(&mut self, output: &Output, index: usize) {
+        self.set_secondary(output, index);
+        self.set_view(View::Workspace);
+    }
+
+    /// Request a window to close.
+    pub fn close(&self, index: usize) {
+        self.windows[index].borrow().surface.send_close();
+    }
+
     /// Application runtime.
     pub fn runtime(&self) -> u32 {
-        self.start_time.elapsed().as_millis() as u32
+        clock::elapsed(self.start_time).as_millis() as u32
     }
 
     /// Check if the overview is currently visible.
@@ -655,6 +1067,53 @@ impl Windows {
         self.start_transaction().view = Some(view);
     }
 
+    /// Index of a window.
+    fn position(&self, window: &Weak<RefCell<Window>>) -> Option<usize> {
+        self.windows.iter().position(|other| Rc::as_ptr(other) == window.as_ptr())
+    }
+
+    /// Index of the next or previous window after the primary one.
+    ///
+    /// Windows on the docked desktop are skipped.
+    fn adjacent_window(&self, forward: bool) -> Option<usize> {
+        let count = self.windows.len();
+        let current = self.position(&self.primary);
+        let on_desktop = |index: usize| {
+            let window = &self.windows[index];
+            self.desktop.iter().any(|desktop| desktop.as_ptr() == Rc::as_ptr(window))
+        };
+
+        (1..=count)
+            .map(|step| match current {
+                Some(index) if forward => (index + step) % count,
+                Some(index) => (index + count - step) % count,
+                None if forward => step - 1,
+                None => count - step,
+            })
+            .find(|&index| Some(index) != current && !on_desktop(index))
+    }
+
+    /// Move a window to the docked desktop.
+    fn add_to_desktop(&mut self, output: &Output, desktop: &Output, index: usize) {
+        let weak = Rc::downgrade(&self.windows[index]);
+
+        // Remove the window from the internal output.
+        let transaction = self.start_transaction();
+        if weak.ptr_eq(&transaction.primary) {
+            self.set_primary(output, None);
+        } else if weak.ptr_eq(&transaction.secondary) {
+            self.set_secondary(output, None);
+        }
+
+        // Append the window as new column.
+        let transaction = self.transaction.get_or_insert(Transaction::new(self));
+        transaction.desktop.push(weak.clone());
+        transaction.update_desktop_dimensions(self.desktop_size);
+
+        self.windows[index].borrow().with_surfaces(|surface, _| desktop.enter(surface));
+        self.focus.toplevel = weak;
+    }
+
     /// Execute a function for all visible windows.
     fn with_visible<F: FnMut(&mut Window)>(&self, mut fun: F) {
         for window in self.primary.upgrade().iter_mut().chain(&mut self.secondary.upgrade()) {
@@ -674,6 +1133,8 @@ impl Windows {
             return;
         }
 
+        transaction.remove_desktop_window(&weak_window, self.desktop_size);
+
         // Update output's visible windows.
         if let Some(primary) = transaction.primary.upgrade() {
             primary.borrow_mut().leave(transaction, output);
@@ -708,6 +1169,8 @@ impl Windows {
             return;
         }
 
+        transaction.remove_desktop_window(&weak_window, self.desktop_size);
+
         // Update output's visible windows.
         if let Some(secondary) = transaction.secondary.upgrade() {
             secondary.borrow_mut().leave(transaction, output);
@@ -732,6 +1195,7 @@ impl Windows {
 pub struct Transaction {
     primary: Weak<RefCell<Window>>,
     secondary: Weak<RefCell<Window>>,
+    desktop: Vec<Weak<RefCell<Window>>>,
     orientation: Orientation,
     view: Option<View>,
     start: Instant,
@@ -742,8 +1206,9 @@ impl Transaction {
         Self {
             primary: current_state.primary.clone(),
             secondary: current_state.secondary.clone(),
+            desktop: current_state.desktop.clone(),
             orientation: current_state.orientation,
-            start: Instant::now(),
+            start: clock::now(),
             view: None,
         }
     }
@@ -761,6 +1226,37 @@ impl Transaction {
             secondary.set_dimensions(self, rectangle);
         }
     }
+
+    /// Tile desktop windows in equally sized columns.
+    pub fn update_desktop_dimensions(&mut self, size: Option<Size<i32, Logical>>) {
+        let size = match size {
+            Some(size) => size,
+            None => return,
+        };
+
+        let windows: Vec<_> = self.desktop.iter().filter_map(Weak::upgrade).collect();
+        let columns = windows.len() as i32;
+        for (column, window) in (0..).zip(&windows) {
+            let x = size.w * column / columns;
+            let width = size.w * (column + 1) / columns - x;
+            let rectangle = Rectangle::from_loc_and_size((x, 0), (width, size.h));
+            window.borrow_mut().set_dimensions(self, rectangle);
+        }
+    }
+
+    /// Remove a window from the docked desktop.
+    fn remove_desktop_window(
+        &mut self,
+        window: &Weak<RefCell<Window>>,
+        desktop_size: Option<Size<i32, Logical>>,
+    ) {
+        let count = self.desktop.len();
+        self.desktop.retain(|desktop_window| !desktop_window.ptr_eq(window));
+
+        if self.desktop.len() != count {
+            self.update_desktop_dimensions(desktop_size);
+        }
+    }
 }
 
 /// Atomic changes to [`Window`].
@@ -991,6 +1487,44 @@ impl Surface for CatacombLayerSurface {
     }
 }
 
+/// Client surface used as pointer cursor image.
+#[derive(Debug)]
+pub struct CursorSurface {
+    surface: WlSurface,
+}
+
+impl From<WlSurface> for CursorSurface {
+    fn from(surface: WlSurface) -> Self {
+        Self { surface }
+    }
+}
+
+impl Surface for CursorSurface {
+    type State = ();
+
+    fn surface(&self) -> Option<&WlSurface> {
+        self.alive().then(|| &self.surface)
+    }
+
+    fn alive(&self) -> bool {
+        self.surface.as_ref().is_alive()
+    }
+
+    fn send_close(&self) {}
+
+    fn set_state<F: FnMut(&mut Self::State)>(&self, _f: F) {}
+
+    fn reconfigure(&self, _size: Size<i32, Logical>) {}
+
+    fn acked_size(&self) -> Size<i32, Logical> {
+        Size::default()
+    }
+
+    fn geometry(&self) -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size((0, 0), (0, 0))
+    }
+}
+
 /// Wayland client window state.
 #[derive(Debug)]
 pub struct Window<S = ToplevelSurface> {
@@ -1154,7 +1688,7 @@ impl<S: Surface> Window<S> {
         compositor::with_surface_tree_upward(
             wl_surface,
             Point::from((0, 0)) - geometry.loc,
-            |_, surface_data, location| {
+            |surface, surface_data, location| {
                 let data = match surface_data.data_map.get::<RefCell<SurfaceBuffer>>() {
                     Some(data) => data,
                     None => return TraversalAction::SkipChildren,
@@ -1198,7 +1732,7 @@ impl<S: Surface> Window<S> {
                         TraversalAction::DoChildren(location)
                     },
                     _ => {
-                        eprintln!("unable to import buffer");
+                        warn!("Unable to import buffer for {surface:?}");
                         data.buffer = None;
 
                         TraversalAction::SkipChildren
@@ -1432,6 +1966,22 @@ impl<S: Surface> Window<S> {
         }
     }
 
+    /// Dismiss the topmost popup.
+    ///
+    /// Returns `false` if the window has no popups.
+    fn dismiss_popup(&self) -> bool {
+        let popup = match self.popups.last() {
+            Some(popup) => popup,
+            None => return false,
+        };
+
+        if !popup.dismiss_popup() {
+            popup.surface.send_popup_done();
+        }
+
+        true
+    }
+
     /// Refresh popup windows.
     fn refresh_popups(&mut self) {
         for i in (0..self.popups.len()).rev() {
@@ -1459,6 +2009,25 @@ impl<S: Surface> Window<S> {
     }
 }
 
+impl Window {
+    /// Get the window's IPC information.
+    fn info(&self) -> WindowInfo {
+        let surface = match self.surface.get_surface() {
+            Some(surface) => surface,
+            None => return WindowInfo::default(),
+        };
+
+        compositor::with_states(surface, |states| {
+            let attributes = states.data_map.get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()?;
+            let attributes = attributes.lock().ok()?;
+            Some(WindowInfo { app_id: attributes.app_id.clone(), title: attributes.title.clone() })
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+    }
+}
+
 impl Window<PopupSurface> {
     /// Get the parent of this popup.
     fn parent(&self) -> Option<WlSurface> {
@@ -1483,6 +2052,33 @@ impl Window<PopupSurface> {
     }
 }
 
+impl Window<CursorSurface> {
+    /// Handle a surface commit for the pointer cursor.
+    pub fn surface_commit(&mut self, surface: &WlSurface, output: &Output) {
+        self.surface_commit_common(surface, output);
+    }
+
+    /// Check if this cursor is using a specific surface.
+    pub fn uses_surface(&self, surface: &WlSurface) -> bool {
+        &self.surface.surface == surface
+    }
+
+    /// Cursor location relative to the surface's origin.
+    pub fn hotspot(&self) -> Point<i32, Logical> {
+        compositor::with_states(&self.surface.surface, |states| {
+            let attributes = states
+                .data_map
+                .get::<Mutex<CursorImageAttributes>>()
+                .and_then(|attributes| attributes.lock().ok());
+
+            attributes.map(|attributes| attributes.hotspot)
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+    }
+}
+
 impl Window<CatacombLayerSurface> {
     /// Handle a surface commit for layer shell windows.
     fn surface_commit(
@@ -1569,6 +2165,16 @@ enum View {
     Workspace,
 }
 
+impl From<View> for IpcView {
+    fn from(view: View) -> Self {
+        match view {
+            View::Overview(_) => IpcView::Overview,
+            View::DragAndDrop(_) => IpcView::DragAndDrop,
+            View::Workspace => IpcView::Workspace,
+        }
+    }
+}
+
 impl Default for View {
     fn default() -> Self {
         View::Workspace
@@ -1602,3 +2208,464 @@ impl Focus {
         self.layer = None;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use smithay::backend::input::ButtonState;
+    use smithay::reexports::wayland_server::protocol::wl_output::Subpixel;
+    use smithay::wayland::output::{Mode, PhysicalProperties};
+    use wayland_client::protocol::wl_pointer;
+    use wayland_protocols::wlr::unstable::layer_shell::v1::client as layer_shell;
+
+    use super::*;
+    use crate::config::{ColorConfig, GestureAction};
+    use crate::test_support::{TestCompositor, TestToplevel};
+
+    /// Check if a window belongs to a client toplevel.
+    fn is_window(window: &Weak<RefCell<Window>>, toplevel: &TestToplevel) -> bool {
+        let window = match window.upgrade() {
+            Some(window) => window,
+            None => return false,
+        };
+        let window = window.borrow();
+        window.surface().map_or(false, |surface| toplevel.is(surface))
+    }
+
+    /// Create a 1920x1080 output with a scale of 2.
+    fn create_output(test: &mut TestCompositor, name: &str) -> Output {
+        let mode = Mode { size: (1920, 1080).into(), refresh: 60_000 };
+        let properties = PhysicalProperties {
+            subpixel: Subpixel::Unknown,
+            model: "Test".into(),
+            make: "Catacomb".into(),
+            size: (0, 0).into(),
+        };
+        let mut display = test.catacomb.display.borrow_mut();
+        Output::new(&mut display, name, mode, properties, 2.)
+    }
+
+    #[test]
+    fn new_toplevel_is_primary() {
+        let mut test = TestCompositor::new();
+        let toplevel = test.create_toplevel("first");
+        test.roundtrip();
+
+        assert!(is_window(&test.catacomb.windows.primary, &toplevel));
+        assert_eq!(test.catacomb.windows.secondary.strong_count(), 0);
+        assert_eq!(toplevel.size(), Some((360, 720)));
+
+        // New windows replace the active ones.
+        let second = test.create_toplevel("second");
+        test.roundtrip();
+
+        assert!(is_window(&test.catacomb.windows.primary, &second));
+        assert_eq!(test.catacomb.windows.secondary.strong_count(), 0);
+        assert_eq!(test.catacomb.windows.window_count(), 2);
+    }
+
+    #[test]
+    fn transaction_waits_for_clients() {
+        let mut test = TestCompositor::new();
+        let toplevel = test.create_toplevel("test");
+        toplevel.state.borrow_mut().ignore_configures = true;
+        test.roundtrip();
+
+        assert!(test.catacomb.windows.transaction.is_some());
+        assert_eq!(test.catacomb.windows.primary.strong_count(), 0);
+
+        // Unresponsive clients cannot block the transaction forever.
+        test.advance_time(MAX_TRANSACTION_DURATION * 2);
+        test.roundtrip();
+
+        assert!(test.catacomb.windows.transaction.is_none());
+        assert!(is_window(&test.catacomb.windows.primary, &toplevel));
+    }
+
+    #[test]
+    fn popup_adoption() {
+        let mut test = TestCompositor::new();
+        let toplevel = test.create_toplevel("test");
+        test.roundtrip();
+
+        let popup = test.create_popup(&toplevel, (100, 50));
+        test.roundtrip();
+
+        let primary = test.catacomb.windows.primary.upgrade().unwrap();
+        assert_eq!(primary.borrow().popups.len(), 1);
+        assert!(test.catacomb.windows.orphan_popups.is_empty());
+        assert!(!popup.state.borrow().closed);
+    }
+
+    #[test]
+    fn popup_of_hidden_window_dismissed() {
+        let mut test = TestCompositor::new();
+        let hidden = test.create_toplevel("hidden");
+        test.roundtrip();
+        let _visible = test.create_toplevel("visible");
+        test.roundtrip();
+
+        let popup = test.create_popup(&hidden, (100, 50));
+        test.roundtrip();
+
+        assert!(popup.state.borrow().closed);
+        assert!(test.catacomb.windows.orphan_popups.is_empty());
+    }
+
+    #[test]
+    fn exclusive_zone_resizes_windows() {
+        let mut test = TestCompositor::new();
+        let toplevel = test.create_toplevel("test");
+        test.roundtrip();
+
+        let layer = layer_shell::zwlr_layer_shell_v1::Layer::Top;
+        let anchor = layer_shell::zwlr_layer_surface_v1::Anchor::Top
+            | layer_shell::zwlr_layer_surface_v1::Anchor::Left
+            | layer_shell::zwlr_layer_surface_v1::Anchor::Right;
+        let layer = test.create_layer(layer, anchor, (0, 20), 20);
+        test.roundtrip();
+
+        assert_eq!(layer.state.borrow().size, Some((360, 20)));
+        assert_eq!(test.catacomb.output.exclusive.top, 20);
+        assert_eq!(toplevel.size(), Some((360, 700)));
+    }
+
+    #[test]
+    fn internal_output_replacement() {
+        let mut test = TestCompositor::new();
+        let toplevel = test.create_toplevel("test");
+        test.roundtrip();
+
+        let output = create_output(&mut test, "HDMIA-1");
+        test.catacomb.replace_internal_output(output);
+        test.roundtrip();
+
+        assert!(is_window(&test.catacomb.windows.primary, &toplevel));
+        assert_eq!(toplevel.size(), Some((960, 540)));
+    }
+
+    #[test]
+    fn disconnected_internal_output() {
+        let mut test = TestCompositor::new();
+        let toplevel = test.create_toplevel("test");
+        test.roundtrip();
+        test.attach_buffer(&toplevel);
+        test.roundtrip();
+
+        // Windows are kept, but receive no frame callbacks without an output.
+        test.catacomb.deactivate_internal_output();
+        let frame_done = test.request_frame(&toplevel);
+        test.roundtrip();
+
+        assert!(!test.catacomb.output.active());
+        assert!(is_window(&test.catacomb.windows.primary, &toplevel));
+        assert_eq!(toplevel.size(), Some((360, 720)));
+        assert!(!frame_done.get());
+
+        // The next connected output takes its place.
+        let output = create_output(&mut test, "HDMIA-1");
+        test.catacomb.replace_internal_output(output);
+        test.roundtrip();
+        test.attach_buffer(&toplevel);
+        test.roundtrip();
+
+        assert!(frame_done.get());
+        assert_eq!(toplevel.size(), Some((960, 540)));
+    }
+
+    #[test]
+    fn docked_desktop_tiling() {
+        let mut test = TestCompositor::new();
+        let phone = test.create_toplevel("phone");
+        test.roundtrip();
+
+        let output = create_output(&mut test, "HDMIA-1");
+        test.catacomb.add_external_output(output);
+        test.catacomb.add_peripheral(String::from("keyboard"));
+        test.roundtrip();
+        assert!(test.catacomb.docked());
+
+        // New windows are tiled on the desktop.
+        let first = test.create_toplevel("first");
+        let second = test.create_toplevel("second");
+        test.roundtrip();
+
+        assert_eq!(test.catacomb.windows.desktop.len(), 2);
+        assert_eq!(first.size(), Some((480, 540)));
+        assert_eq!(second.size(), Some((480, 540)));
+        assert!(is_window(&test.catacomb.windows.primary, &phone));
+
+        // Undocking moves all windows back to the internal output.
+        test.catacomb.remove_peripheral("keyboard");
+        test.roundtrip();
+
+        assert!(!test.catacomb.docked());
+        assert!(test.catacomb.windows.desktop.is_empty());
+        assert_eq!(first.size(), Some((360, 720)));
+        assert!(is_window(&test.catacomb.windows.primary, &phone));
+    }
+
+    #[test]
+    fn pointer_focus_and_buttons() {
+        const BTN_LEFT: u32 = 0x110;
+
+        let mut test = TestCompositor::new();
+        let toplevel = test.create_toplevel("test");
+        test.roundtrip();
+        test.attach_buffer(&toplevel);
+        let pointer = test.bind_pointer();
+
+        test.pointer_motion((100., 100.));
+        test.roundtrip();
+
+        assert_eq!(pointer.borrow().focus, Some(toplevel.id()));
+
+        test.pointer_button(BTN_LEFT, ButtonState::Pressed);
+        test.pointer_button(BTN_LEFT, ButtonState::Released);
+        test.roundtrip();
+
+        let buttons = &pointer.borrow().buttons;
+        assert_eq!(buttons, &[
+            (BTN_LEFT, wl_pointer::ButtonState::Pressed),
+            (BTN_LEFT, wl_pointer::ButtonState::Released),
+        ]);
+    }
+
+    #[test]
+    fn overview_gesture() {
+        let mut test = TestCompositor::new();
+        let _toplevel = test.create_toplevel("test");
+        test.roundtrip();
+
+        test.swipe((355., 715.), (100., 100.));
+        test.roundtrip();
+
+        assert!(matches!(test.catacomb.windows.view, View::Overview(_)));
+    }
+
+    #[test]
+    fn overview_capture() {
+        let mut test = TestCompositor::new();
+        let toplevel = test.create_toplevel("test");
+        test.roundtrip();
+        test.attach_buffer(&toplevel);
+        test.roundtrip();
+
+        test.swipe((355., 715.), (100., 100.));
+        test.roundtrip();
+
+        // Skip rendering without EGL support.
+        let capture = match test.capture() {
+            Some(capture) => capture,
+            None => return,
+        };
+
+        // Transparent window content shows the decoration's background.
+        let colors = ColorConfig::default();
+        assert_eq!(capture.pixel((360, 720)), colors.background.rgba());
+
+        // Titlebar is centered above the window.
+        let output = &test.catacomb.output;
+        let title_height = Graphics::title_height(output);
+        let window_y = (720 - 540 + title_height + Graphics::border_width(output)) / 2;
+        let title_y = (window_y - title_height / 2) * 2;
+        assert_eq!(capture.pixel((360, title_y)), colors.title.rgba());
+
+        // Space next to the window is cleared.
+        assert_eq!(capture.pixel((10, 720)), [255, 0, 255, 255]);
+    }
+
+    #[test]
+    fn multi_finger_overview_gesture() {
+        let mut test = TestCompositor::new();
+        let _toplevel = test.create_toplevel("test");
+        test.roundtrip();
+
+        let fingers = [(0, 100.), (1, 180.), (2, 260.)];
+        for (slot, x) in fingers {
+            test.touch_down(slot, (x, 500.));
+        }
+        test.touch_frame();
+        for (slot, x) in fingers {
+            test.touch_motion(slot, (x, 300.));
+        }
+        test.touch_frame();
+        for (slot, _) in fingers {
+            test.touch_up(slot);
+        }
+        test.touch_frame();
+        test.roundtrip();
+
+        assert!(matches!(test.catacomb.windows.view, View::Overview(_)));
+    }
+
+    #[test]
+    fn two_finger_window_switch() {
+        let mut test = TestCompositor::new();
+        let first = test.create_toplevel("first");
+        let second = test.create_toplevel("second");
+        test.roundtrip();
+
+        test.touch_down(0, (300., 400.));
+        test.touch_down(1, (400., 400.));
+        test.touch_frame();
+        test.touch_motion(0, (100., 400.));
+        test.touch_motion(1, (200., 400.));
+        test.touch_frame();
+        test.touch_up(0);
+        test.touch_up(1);
+        test.touch_frame();
+        test.roundtrip();
+
+        assert!(is_window(&test.catacomb.windows.primary, &first));
+        assert_eq!(test.catacomb.windows.secondary.strong_count(), 0);
+
+        // Switching back returns to the previous window.
+        test.touch_down(0, (100., 400.));
+        test.touch_down(1, (200., 400.));
+        test.touch_frame();
+        test.touch_motion(0, (300., 400.));
+        test.touch_motion(1, (400., 400.));
+        test.touch_frame();
+        test.touch_up(0);
+        test.touch_up(1);
+        test.touch_frame();
+        test.roundtrip();
+
+        assert!(is_window(&test.catacomb.windows.primary, &second));
+    }
+
+    #[test]
+    fn two_finger_pan() {
+        let mut test = TestCompositor::new();
+        let _first = test.create_toplevel("first");
+        let second = test.create_toplevel("second");
+        test.roundtrip();
+
+        // Short pans are left to the client.
+        test.touch_down(0, (200., 400.));
+        test.touch_down(1, (300., 400.));
+        test.touch_frame();
+        test.touch_motion(0, (100., 400.));
+        test.touch_motion(1, (200., 400.));
+        test.touch_frame();
+        test.touch_up(0);
+        test.touch_up(1);
+        test.touch_frame();
+        test.roundtrip();
+
+        assert!(is_window(&test.catacomb.windows.primary, &second));
+    }
+
+    #[test]
+    fn home_gesture() {
+        let mut test = TestCompositor::new();
+        let _toplevel = test.create_toplevel("test");
+        test.roundtrip();
+
+        test.swipe((180., 715.), (180., 100.));
+        test.roundtrip();
+
+        assert_eq!(test.catacomb.windows.view, View::Workspace);
+        assert_eq!(test.catacomb.windows.primary.strong_count(), 0);
+        assert_eq!(test.catacomb.windows.window_count(), 1);
+    }
+
+    #[test]
+    fn landscape_home_gesture() {
+        let mut test = TestCompositor::new();
+        let _toplevel = test.create_toplevel("test");
+        test.roundtrip();
+
+        test.catacomb.handle_orientation(Orientation::Landscape);
+        test.roundtrip();
+
+        // The device's portrait bottom is now at the side of the output.
+        test.swipe((715., 180.), (400., 180.));
+        test.roundtrip();
+
+        assert_eq!(test.catacomb.windows.primary.strong_count(), 1);
+
+        // The bottom edge is the bottom of the rotated output.
+        test.swipe((360., 355.), (360., 100.));
+        test.roundtrip();
+
+        assert_eq!(test.catacomb.windows.primary.strong_count(), 0);
+    }
+
+    #[test]
+    fn edge_gesture_toggles_secondary() {
+        let mut test = TestCompositor::new();
+        let first = test.create_toplevel("first");
+        let _second = test.create_toplevel("second");
+        test.roundtrip();
+
+        test.catacomb.config.gestures.edges.left = GestureAction::ToggleSecondary;
+
+        test.swipe((2., 360.), (200., 360.));
+        test.roundtrip();
+
+        assert!(is_window(&test.catacomb.windows.secondary, &first));
+
+        test.swipe((2., 360.), (200., 360.));
+        test.roundtrip();
+
+        assert_eq!(test.catacomb.windows.secondary.strong_count(), 0);
+    }
+
+    #[test]
+    fn back_gesture_dismisses_popup() {
+        let mut test = TestCompositor::new();
+        let toplevel = test.create_toplevel("test");
+        test.roundtrip();
+
+        let popup = test.create_popup(&toplevel, (100, 50));
+        test.roundtrip();
+
+        test.swipe((2., 360.), (200., 360.));
+        test.roundtrip();
+
+        assert!(popup.state.borrow().closed);
+    }
+
+    #[test]
+    fn cancelled_touch_ignores_gesture() {
+        let mut test = TestCompositor::new();
+        let _toplevel = test.create_toplevel("test");
+        test.roundtrip();
+
+        test.touch_down(0, (180., 715.));
+        test.touch_frame();
+        test.touch_motion(0, (180., 100.));
+        test.touch_frame();
+        test.touch_cancel(0);
+        test.roundtrip();
+
+        assert_eq!(test.catacomb.windows.primary.strong_count(), 1);
+    }
+
+    #[test]
+    fn cancelled_overview_drag_keeps_window() {
+        let mut test = TestCompositor::new();
+        let toplevel = test.create_toplevel("test");
+        test.roundtrip();
+
+        test.swipe((355., 715.), (100., 100.));
+        test.roundtrip();
+        assert!(matches!(test.catacomb.windows.view, View::Overview(_)));
+
+        // Drag the window far enough to close it, then cancel.
+        test.touch_down(0, (180., 360.));
+        test.touch_frame();
+        test.touch_motion(0, (180., 20.));
+        test.touch_frame();
+        test.touch_cancel(0);
+        test.roundtrip();
+
+        assert_eq!(test.catacomb.windows.window_count(), 1);
+        assert!(!toplevel.state.borrow().closed);
+        match test.catacomb.windows.view {
+            View::Overview(overview) => assert_eq!(overview.y_offset, 0.),
+            view => panic!("expected overview, got {view:?}"),
+        }
+    }
+}
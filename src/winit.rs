@@ -3,6 +3,7 @@ use std::error::Error;
 use std::rc::Rc;
 use std::time::Duration;
 
+use log::error;
 use smithay::backend::renderer::gles2::{Gles2Frame, Gles2Renderer};
 use smithay::backend::renderer::{ImportDma, ImportEgl, Renderer, TextureFilter};
 use smithay::backend::winit::{self, WinitGraphicsBackend};
@@ -11,7 +12,9 @@ use smithay::utils::Transform;
 use smithay::wayland::dmabuf;
 use smithay::wayland::output::Mode;
 
-use crate::catacomb::{Backend, Catacomb, Render};
+use crate::catacomb::{Backend, Catacomb, Render, Startup};
+use crate::config::Config;
+use crate::logging;
 
 struct Winit;
 
@@ -21,14 +24,15 @@ impl Backend for Winit {
     }
 }
 
-pub fn run() {
-    let (graphics, mut input) = winit::init(None).expect("init winit");
+pub fn run(config: Config, startup: Startup, touch_emulation: bool) -> Result<(), Box<dyn Error>> {
+    let (graphics, mut input) = winit::init(logging::smithay())?;
     let graphics = Rc::new(RefCell::new(graphics));
-    graphics.borrow_mut().bind().expect("binding renderer");
+    graphics.borrow_mut().bind()?;
     let _ = graphics.borrow_mut().renderer().downscale_filter(TextureFilter::Linear);
 
-    let mut event_loop = EventLoop::try_new().expect("event loop");
-    let mut catacomb = Catacomb::new(&mut event_loop, Winit);
+    let mut event_loop = EventLoop::try_new()?;
+    let mut catacomb = Catacomb::new(&mut event_loop, config, startup, Winit)?;
+    catacomb.touch_emulation = touch_emulation;
 
     // Set the output size.
     let mode = Mode { size: graphics.borrow().window_size().physical_size, refresh: 200_000 };
@@ -43,14 +47,14 @@ pub fn run() {
             &mut egl_display.borrow_mut(),
             formats,
             move |buffer, _| graphics.borrow_mut().renderer().import_dmabuf(buffer, None).is_ok(),
-            None,
+            logging::smithay(),
         );
     }
 
     let display = catacomb.display.clone();
     loop {
         if input.dispatch_new_events(|event| catacomb.handle_winit_input(event)).is_err() {
-            eprintln!("input error");
+            error!("Winit input error");
             break;
         }
 
@@ -60,12 +64,14 @@ pub fn run() {
         // it would reduce the framerate, while decreasing it would mean that most of
         // the vblank interval is spent not doing anything, rather than handling events.
         if event_loop.dispatch(Some(Duration::from_millis(5)), &mut catacomb).is_err() {
-            eprintln!("event loop error");
+            error!("Event loop error");
             break;
         }
 
         display.borrow_mut().flush_clients(&mut catacomb);
     }
+
+    Ok(())
 }
 
 impl Render for &mut WinitGraphicsBackend {
@@ -80,12 +86,10 @@ impl Render for &mut WinitGraphicsBackend {
         let logical_size = catacomb.output.resolution().to_f64();
         let output_size = logical_size.to_physical(catacomb.output.scale()).to_i32_round();
         let buffer_age = self.buffer_age().unwrap_or(0) as u8;
-        self.renderer()
-            .render(output_size, Transform::Flipped180, |renderer, frame| {
-                draw_fun(catacomb, renderer, frame, buffer_age);
-            })
-            .expect("render");
-        self.submit(None, 1.0).expect("submit");
+        self.renderer().render(output_size, Transform::Flipped180, |renderer, frame| {
+            draw_fun(catacomb, renderer, frame, buffer_age);
+        })?;
+        self.submit(None, 1.0)?;
         Ok(())
     }
 }